@@ -1,9 +1,220 @@
-use itertools::Itertools;
+use std::fmt;
+use std::path::Path;
+
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::architecture::Architecture;
+use crate::utils;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(from = "BootFileEntry", into = "BootFileEntry")]
+pub struct BootFile {
+    pub source: String,
+    pub destination: String,
+    pub checksum: Option<String>,
+    pub architecture: Option<Architecture>,
+    pub model: Option<String>,
+}
+
+/// How a boot file is stored: by its name alone, as it was before files had options, or with
+/// every field.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum BootFileEntry {
+    Name(String),
+    Entry {
+        source: String,
+        destination: String,
+        checksum: Option<String>,
+        architecture: Option<Architecture>,
+        model: Option<String>,
+    },
+}
+
+impl From<BootFileEntry> for BootFile {
+    fn from(entry: BootFileEntry) -> Self {
+        match entry {
+            BootFileEntry::Name(name) => BootFile::new(&name, &name),
+            BootFileEntry::Entry {
+                source,
+                destination,
+                checksum,
+                architecture,
+                model,
+            } => BootFile {
+                source,
+                destination,
+                checksum,
+                architecture,
+                model,
+            },
+        }
+    }
+}
+
+impl From<BootFile> for BootFileEntry {
+    fn from(file: BootFile) -> Self {
+        if file.is_plain() {
+            return BootFileEntry::Name(file.source);
+        }
+        BootFileEntry::Entry {
+            source: file.source,
+            destination: file.destination,
+            checksum: file.checksum,
+            architecture: file.architecture,
+            model: file.model,
+        }
+    }
+}
+
+impl BootFile {
+    #[must_use]
+    pub fn new(source: &str, destination: &str) -> Self {
+        BootFile {
+            source: String::from(source),
+            destination: String::from(destination),
+            checksum: None,
+            architecture: None,
+            model: None,
+        }
+    }
+
+    /// Returns the token this file is configured with; just the name if it is copied to the
+    /// same path without any options.
+    #[must_use]
+    pub fn get_name(&self) -> String {
+        if self.is_plain() {
+            self.source.clone()
+        } else {
+            self.to_string()
+        }
+    }
+
+    /// Returns whether the file is copied to the same path without any options.
+    fn is_plain(&self) -> bool {
+        self.source.eq(&self.destination)
+            && self.checksum.is_none()
+            && self.architecture.is_none()
+            && self.model.is_none()
+    }
+
+    /// Parses `source[:destination][;sha256=<checksum>][;arch=<architecture>][;model=<model>]`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `token` could not be parsed
+    pub fn parse(token: &str) -> Result<BootFile, &'static str> {
+        let mut parts = token.split(';');
+        let mut paths = parts.next().unwrap_or_default().splitn(2, ':');
+        let source = paths.next().unwrap_or_default();
+        let destination = paths.next().unwrap_or(source);
+        if source.is_empty() || destination.is_empty() {
+            return Err("Could not parse BootFile");
+        }
+        let mut file = BootFile::new(source, destination);
+        for qualifier in parts {
+            match qualifier.split_once('=') {
+                Some(("sha256", checksum)) if !checksum.is_empty() => {
+                    file.checksum = Some(checksum.to_lowercase());
+                }
+                Some(("arch", architecture)) => {
+                    file.architecture = Some(Architecture::parse(architecture)?);
+                }
+                Some(("model", model)) if !model.is_empty() => {
+                    file.model = Some(model.to_string());
+                }
+                _ => return Err("Could not parse BootFile qualifier"),
+            }
+        }
+        Ok(file)
+    }
+
+    #[must_use]
+    pub fn applies_to(&self, architecture: &Architecture, model: Option<&str>) -> bool {
+        let architecture_matches = match &self.architecture {
+            Some(a) => a.eq(architecture),
+            None => true,
+        };
+        let model_matches = match (&self.model, model) {
+            (Some(m), Some(model)) => m.eq(model),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        architecture_matches && model_matches
+    }
+
+    fn overlaps(&self, other: &BootFile) -> bool {
+        let architecture = match (&self.architecture, &other.architecture) {
+            (Some(a), Some(b)) => a.eq(b),
+            _ => true,
+        };
+        let model = match (&self.model, &other.model) {
+            (Some(a), Some(b)) => a.eq(b),
+            _ => true,
+        };
+        architecture && model
+    }
+
+    /// Copies the file from the image tree at `source_root` to `destination_root`, verifying
+    /// its checksum if one is set.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the file could not be copied or its checksum does not match
+    pub fn install(&self, source_root: &str, destination_root: &str) -> Result<(), String> {
+        let source = Path::new(source_root).join(self.source.trim_start_matches('/'));
+        let destination =
+            Path::new(destination_root).join(self.destination.trim_start_matches('/'));
+        let source_path = source.to_string_lossy().to_string();
+        if let Some(checksum) = &self.checksum {
+            if !utils::sha256sum_matches(&source_path, checksum) {
+                return Err(format!("checksum of {} does not match", source_path));
+            }
+        }
+        if let Some(parent) = destination.parent() {
+            utils::create_directory(parent).map_err(|e| e.to_string())?;
+        }
+        utils::copy_file(&source, &destination)
+            .map_err(|e| format!("can not copy {}: {}", source_path, e))
+    }
+}
+
+impl fmt::Display for BootFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.source, self.destination)?;
+        if let Some(checksum) = &self.checksum {
+            write!(f, ";sha256={}", checksum)?;
+        }
+        if let Some(architecture) = &self.architecture {
+            write!(f, ";arch={}", architecture.get_name())?;
+        }
+        if let Some(model) = &self.model {
+            write!(f, ";model={}", model)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BootConflict {
+    pub destination: String,
+    pub first: BootFile,
+    pub second: BootFile,
+}
+
+impl fmt::Display for BootConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is provided by both {} and {}",
+            self.destination, self.first, self.second
+        )
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BootConfig {
-    files: Vec<String>,
+    files: Vec<BootFile>,
 }
 
 impl BootConfig {
@@ -18,18 +229,65 @@ impl BootConfig {
     pub fn parse(line: &str) -> Result<BootConfig, &'static str> {
         let files = line
             .split_whitespace()
-            .map(std::string::ToString::to_string)
-            .collect::<Vec<String>>();
+            .map(BootFile::parse)
+            .collect::<Result<Vec<BootFile>, &'static str>>()?;
         if !files.is_empty() {
             return Ok(BootConfig { files });
         }
         Err("Could not parse BootConfig")
     }
 
+    /// Returns the files as configured, a plain name for files copied to the same path.
     #[must_use]
     pub fn get_files(&self) -> Vec<String> {
+        self.files.iter().map(BootFile::get_name).collect()
+    }
+
+    /// Returns the paths the files are copied from in the image tree.
+    #[must_use]
+    pub fn get_sources(&self) -> Vec<String> {
+        self.files.iter().map(|f| f.source.clone()).collect()
+    }
+
+    #[must_use]
+    pub fn get_entries(&self) -> Vec<BootFile> {
         self.files.clone()
     }
+
+    #[must_use]
+    pub fn get_entries_for(
+        &self,
+        architecture: &Architecture,
+        model: Option<&str>,
+    ) -> Vec<BootFile> {
+        self.files
+            .iter()
+            .filter(|f| f.applies_to(architecture, model))
+            .cloned()
+            .collect()
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` with a message for every file that could not be installed
+    pub fn install(
+        &self,
+        source_root: &str,
+        destination_root: &str,
+        architecture: &Architecture,
+        model: Option<&str>,
+    ) -> Result<(), Vec<String>> {
+        let errors = self
+            .get_entries_for(architecture, model)
+            .iter()
+            .filter_map(|f| f.install(source_root, destination_root).err())
+            .collect::<Vec<String>>();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
 }
 
 impl Default for BootConfig {
@@ -38,12 +296,35 @@ impl Default for BootConfig {
     }
 }
 
-#[must_use]
-pub fn group(bootconfigs: &[BootConfig]) -> BootConfig {
-    let mut bootconfig = BootConfig { files: Vec::new() };
-    bootconfigs
-        .iter()
-        .for_each(|b| bootconfig.files.append(&mut b.files.clone()));
-    bootconfig.files = bootconfig.files.into_iter().unique().collect();
-    bootconfig
+/// # Errors
+///
+/// Will return `Err` with every destination that is mapped to different sources or checksums
+/// within an overlapping architecture and model scope
+pub fn group(bootconfigs: &[BootConfig]) -> Result<BootConfig, Vec<BootConflict>> {
+    let mut files: Vec<BootFile> = Vec::new();
+    for file in bootconfigs.iter().flat_map(|b| b.files.iter()) {
+        if !files.contains(file) {
+            files.push(file.clone());
+        }
+    }
+    let mut conflicts = Vec::new();
+    for (i, first) in files.iter().enumerate() {
+        for second in &files[i + 1..] {
+            if first.destination.eq(&second.destination)
+                && first.overlaps(second)
+                && (!first.source.eq(&second.source) || !first.checksum.eq(&second.checksum))
+            {
+                conflicts.push(BootConflict {
+                    destination: first.destination.clone(),
+                    first: first.clone(),
+                    second: second.clone(),
+                });
+            }
+        }
+    }
+    if conflicts.is_empty() {
+        Ok(BootConfig { files })
+    } else {
+        Err(conflicts)
+    }
 }
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use config::Value;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, Eq, PartialEq)]
+pub struct Capacity {
+    pub cpu_cores: Option<u32>,
+    pub memory_mb: Option<u64>,
+    pub disk_mb: Option<u64>,
+    pub disk_type: Option<String>,
+    pub board_model: Option<String>,
+}
+
+pub const KEYS: [&str; 5] = ["cpu-cores", "memory", "disk", "disk-type", "board-model"];
+
+impl Capacity {
+    /// # Errors
+    ///
+    /// Will return `Err` with the key and message of every invalid value in `hash`
+    pub fn from_config(hash: &HashMap<String, Value>) -> Result<Self, Vec<(String, String)>> {
+        let mut errors = Vec::new();
+        let mut capacity = Capacity::default();
+        for key in &KEYS {
+            let value = match hash.get(*key) {
+                Some(value) => match value.clone().into_str() {
+                    Ok(value) => value,
+                    Err(e) => {
+                        errors.push((key.to_string(), e.to_string()));
+                        continue;
+                    }
+                },
+                None => continue,
+            };
+            let result = match *key {
+                "cpu-cores" => value
+                    .parse::<u32>()
+                    .map(|v| capacity.cpu_cores = Some(v))
+                    .map_err(|_| format!("{} is not a number of cores", value)),
+                "memory" => parse_size(&value).map(|v| capacity.memory_mb = Some(v)),
+                "disk" => parse_size(&value).map(|v| capacity.disk_mb = Some(v)),
+                "disk-type" => {
+                    capacity.disk_type = Some(value.to_lowercase());
+                    Ok(())
+                }
+                _ => {
+                    capacity.board_model = Some(value);
+                    Ok(())
+                }
+            };
+            if let Err(e) = result {
+                errors.push((key.to_string(), e));
+            }
+        }
+        if errors.is_empty() {
+            Ok(capacity)
+        } else {
+            Err(errors)
+        }
+    }
+
+    #[must_use]
+    pub fn get_values(&self) -> Vec<(&'static str, String)> {
+        let mut values = Vec::new();
+        if let Some(cores) = self.cpu_cores {
+            values.push(("cpu-cores", cores.to_string()));
+        }
+        if let Some(memory) = self.memory_mb {
+            values.push(("memory", format_size(memory)));
+        }
+        if let Some(disk) = self.disk_mb {
+            values.push(("disk", format_size(disk)));
+        }
+        if let Some(disk_type) = &self.disk_type {
+            values.push(("disk-type", disk_type.clone()));
+        }
+        if let Some(board_model) = &self.board_model {
+            values.push(("board-model", board_model.clone()));
+        }
+        values
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.get_values().is_empty()
+    }
+}
+
+impl fmt::Display for Capacity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let values = self
+            .get_values()
+            .into_iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<String>>();
+        write!(f, "{}", values.join(" "))
+    }
+}
+
+/// Parses sizes such as `512M`, `8G`, `8GB`, `1TiB` or a plain number of megabytes.
+///
+/// # Errors
+///
+/// Will return `Err` if `value` is not a size
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let trimmed = value.trim();
+    let split = trimmed
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+    let number = number
+        .parse::<u64>()
+        .map_err(|_| format!("{} is not a size", value))?;
+    let factor = match unit.trim().to_uppercase().as_str() {
+        "" | "M" | "MB" | "MIB" => 1,
+        "G" | "GB" | "GIB" => 1024,
+        "T" | "TB" | "TIB" => 1024 * 1024,
+        _ => return Err(format!("{} is not a size", value)),
+    };
+    Ok(number * factor)
+}
+
+#[must_use]
+pub fn format_size(megabytes: u64) -> String {
+    for (unit, factor) in &[("T", 1024 * 1024), ("G", 1024)] {
+        if megabytes >= *factor && megabytes / factor * factor == megabytes {
+            return format!("{}{}", megabytes / factor, unit);
+        }
+    }
+    format!("{}M", megabytes)
+}
@@ -0,0 +1,148 @@
+use std::fmt;
+use std::fs;
+use std::io;
+
+use crate::utils;
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+enum Token {
+    Parameter(String, String),
+    Flag(String),
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub struct CmdlineTxt {
+    tokens: Vec<Token>,
+}
+
+impl CmdlineTxt {
+    #[must_use]
+    pub fn new() -> Self {
+        CmdlineTxt { tokens: Vec::new() }
+    }
+
+    #[must_use]
+    pub fn parse(line: &str) -> Self {
+        let tokens = line
+            .split_whitespace()
+            .map(|token| match token.split_once('=') {
+                Some((key, value)) => Token::Parameter(key.to_string(), value.to_string()),
+                None => Token::Flag(token.to_string()),
+            })
+            .collect::<Vec<Token>>();
+        CmdlineTxt { tokens }
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if `filename` could not be read
+    pub fn read(filename: &str) -> io::Result<Self> {
+        Ok(CmdlineTxt::parse(&fs::read_to_string(filename)?))
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if `filename` could not be written to
+    pub fn write(&self, filename: &str) -> io::Result<()> {
+        utils::write_file(filename, &format!("{}\n", self))
+    }
+
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.get_all(key).pop()
+    }
+
+    #[must_use]
+    pub fn get_all(&self, key: &str) -> Vec<String> {
+        self.tokens
+            .iter()
+            .filter_map(|t| match t {
+                Token::Parameter(k, v) if k.eq(key) => Some(v.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[must_use]
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.tokens.contains(&Token::Flag(flag.to_string()))
+    }
+
+    /// Replaces every occurrence of `key` with a single `key=value` parameter, keeping the
+    /// position of the first occurrence.
+    pub fn set(&mut self, key: &str, value: &str) {
+        let parameter = Token::Parameter(key.to_string(), value.to_string());
+        let positions = self.positions_of(key);
+        match positions.split_first() {
+            Some((first, rest)) => {
+                self.tokens[*first] = parameter;
+                for index in rest.iter().rev() {
+                    self.tokens.remove(*index);
+                }
+            }
+            None => self.tokens.push(parameter),
+        }
+    }
+
+    /// Appends `key=value` unless it is already present, e.g. for a second `console=`.
+    pub fn add(&mut self, key: &str, value: &str) {
+        let parameter = Token::Parameter(key.to_string(), value.to_string());
+        if !self.tokens.contains(&parameter) {
+            self.tokens.push(parameter);
+        }
+    }
+
+    pub fn set_flag(&mut self, flag: &str) {
+        if !self.has_flag(flag) {
+            self.tokens.push(Token::Flag(flag.to_string()));
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.tokens.retain(|t| match t {
+            Token::Parameter(k, _) | Token::Flag(k) => !k.eq(key),
+        });
+    }
+
+    /// Applies the tokens of `other` on top of this command line. Flags are added and
+    /// parameters replace all values of the same key.
+    pub fn merge(&mut self, other: &CmdlineTxt) {
+        let mut replaced: Vec<&str> = Vec::new();
+        for token in &other.tokens {
+            match token {
+                Token::Parameter(key, value) => {
+                    if replaced.contains(&key.as_str()) {
+                        self.add(key, value);
+                    } else {
+                        self.set(key, value);
+                        replaced.push(key.as_str());
+                    }
+                }
+                Token::Flag(flag) => self.set_flag(flag),
+            }
+        }
+    }
+
+    fn positions_of(&self, key: &str) -> Vec<usize> {
+        self.tokens
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| matches!(t, Token::Parameter(k, _) if k.eq(key)))
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
+
+impl fmt::Display for CmdlineTxt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tokens = self
+            .tokens
+            .iter()
+            .map(|t| match t {
+                Token::Parameter(key, value) => format!("{}={}", key, value),
+                Token::Flag(flag) => flag.clone(),
+            })
+            .collect::<Vec<String>>();
+        write!(f, "{}", tokens.join(" "))
+    }
+}
@@ -0,0 +1,369 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::process::Command;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::Duration;
+
+use crate::utils;
+
+/// The runner used unless one is given explicitly; `None` means a plain [`LocalRunner`].
+static RUNNER: RwLock<Option<Arc<dyn CommandRunner>>> = RwLock::new(None);
+
+/// What a command printed and how it exited.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct CommandOutput {
+    /// The exit code, or `None` if the command was killed by a signal.
+    pub code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl CommandOutput {
+    #[must_use]
+    pub fn new(code: i32, stdout: &str) -> Self {
+        CommandOutput {
+            code: Some(code),
+            stdout: String::from(stdout),
+            stderr: String::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+/// Something that runs programs, on this host or elsewhere.
+pub trait CommandRunner: fmt::Debug + Send + Sync {
+    /// Runs `program` with `arguments` and kills it once `timeout` has passed, if given.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the program could not be started or did not exit in time, with
+    /// `ErrorKind::TimedOut` in the latter case
+    fn run(
+        &self,
+        program: &str,
+        arguments: &[String],
+        timeout: Option<Duration>,
+    ) -> io::Result<CommandOutput>;
+
+    fn get_name(&self) -> String;
+}
+
+/// Runs programs on this host, optionally behind a prefix such as `sudo -n`.
+#[derive(Debug, Clone, Default)]
+pub struct LocalRunner {
+    pub prefix: Vec<String>,
+}
+
+impl LocalRunner {
+    #[must_use]
+    pub fn with_prefix(prefix: &[&str]) -> Self {
+        LocalRunner {
+            prefix: prefix.iter().map(|p| (*p).to_string()).collect(),
+        }
+    }
+}
+
+impl CommandRunner for LocalRunner {
+    fn run(
+        &self,
+        program: &str,
+        arguments: &[String],
+        timeout: Option<Duration>,
+    ) -> io::Result<CommandOutput> {
+        let mut command = match self.prefix.split_first() {
+            Some((first, rest)) => {
+                let mut command = Command::new(first);
+                command.args(rest).arg(program);
+                command
+            }
+            None => Command::new(program),
+        };
+        command.args(arguments);
+        let output = match timeout {
+            Some(timeout) => utils::run_with_timeout(&mut command, timeout)?,
+            None => command.output()?,
+        };
+        Ok(CommandOutput {
+            code: output.status.code(),
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        })
+    }
+
+    fn get_name(&self) -> String {
+        if self.prefix.is_empty() {
+            String::from("local")
+        } else {
+            self.prefix.join(" ")
+        }
+    }
+}
+
+/// Runs programs on another host over SSH with key based authentication.
+#[derive(Debug, Clone)]
+pub struct SshRunner {
+    pub host: String,
+    pub user: Option<String>,
+    pub port: u16,
+    pub options: Vec<String>,
+}
+
+impl SshRunner {
+    #[must_use]
+    pub fn new(host: &str) -> Self {
+        SshRunner {
+            host: String::from(host),
+            user: None,
+            port: 22,
+            options: vec![String::from("BatchMode=yes")],
+        }
+    }
+
+    /// Returns the arguments for `ssh` that run `program` remotely, quoted for the remote
+    /// shell.
+    #[must_use]
+    pub fn get_arguments(&self, program: &str, arguments: &[String]) -> Vec<String> {
+        let mut ssh_arguments = Vec::new();
+        for option in &self.options {
+            ssh_arguments.push(String::from("-o"));
+            ssh_arguments.push(option.clone());
+        }
+        ssh_arguments.push(String::from("-p"));
+        ssh_arguments.push(self.port.to_string());
+        ssh_arguments.push(match &self.user {
+            Some(user) => format!("{}@{}", user, self.host),
+            None => self.host.clone(),
+        });
+        ssh_arguments.push(String::from("--"));
+        let mut remote = vec![quote(program)];
+        remote.extend(arguments.iter().map(|a| quote(a)));
+        ssh_arguments.push(remote.join(" "));
+        ssh_arguments
+    }
+}
+
+impl CommandRunner for SshRunner {
+    fn run(
+        &self,
+        program: &str,
+        arguments: &[String],
+        timeout: Option<Duration>,
+    ) -> io::Result<CommandOutput> {
+        LocalRunner::default().run("ssh", &self.get_arguments(program, arguments), timeout)
+    }
+
+    fn get_name(&self) -> String {
+        match &self.user {
+            Some(user) => format!("ssh://{}@{}:{}", user, self.host, self.port),
+            None => format!("ssh://{}:{}", self.host, self.port),
+        }
+    }
+}
+
+/// Runs nothing but records every call and answers with the output set for the program, or
+/// with success and no output.
+#[derive(Debug, Default)]
+pub struct RecordingRunner {
+    calls: Mutex<Vec<Vec<String>>>,
+    outputs: HashMap<String, CommandOutput>,
+}
+
+impl RecordingRunner {
+    #[must_use]
+    pub fn new() -> Self {
+        RecordingRunner::default()
+    }
+
+    #[must_use]
+    pub fn with_output(mut self, program: &str, output: CommandOutput) -> Self {
+        self.outputs.insert(String::from(program), output);
+        self
+    }
+
+    /// Returns every call so far as the program followed by its arguments.
+    #[must_use]
+    pub fn get_calls(&self) -> Vec<Vec<String>> {
+        self.calls
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+impl CommandRunner for RecordingRunner {
+    fn run(
+        &self,
+        program: &str,
+        arguments: &[String],
+        _timeout: Option<Duration>,
+    ) -> io::Result<CommandOutput> {
+        let mut call = vec![String::from(program)];
+        call.extend(arguments.iter().cloned());
+        self.calls
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(call);
+        Ok(self
+            .outputs
+            .get(program)
+            .cloned()
+            .unwrap_or_else(|| CommandOutput::new(0, "")))
+    }
+
+    fn get_name(&self) -> String {
+        String::from("recording")
+    }
+}
+
+/// Creates a runner from `local`, `sudo` or `ssh://[user@]host[:port]`.
+///
+/// # Errors
+///
+/// Will return `Err` if `line` is none of these
+pub fn parse(line: &str) -> Result<Arc<dyn CommandRunner>, String> {
+    match line.trim() {
+        "local" => return Ok(Arc::new(LocalRunner::default())),
+        "sudo" => return Ok(Arc::new(LocalRunner::with_prefix(&["sudo", "-n"]))),
+        _ => {}
+    }
+    let target = line
+        .trim()
+        .strip_prefix("ssh://")
+        .ok_or_else(|| format!("{} is not a command runner", line))?;
+    let (user, address) = match target.split_once('@') {
+        Some((user, address)) => (Some(String::from(user)), address),
+        None => (None, target),
+    };
+    let (host, port) = match address.rsplit_once(':') {
+        Some((host, port)) => (
+            host,
+            port.parse::<u16>()
+                .map_err(|_| format!("{} is not a port", port))?,
+        ),
+        None => (address, 22),
+    };
+    if host.is_empty() {
+        return Err(format!("{} has no host", line));
+    }
+    let mut runner = SshRunner::new(host);
+    runner.user = user;
+    runner.port = port;
+    Ok(Arc::new(runner))
+}
+
+/// Makes `runner` the one power and probe commands are run with. Mounting and hashing files
+/// act on this host and run with a [`LocalRunner`] unless given a runner of their own.
+pub fn set_runner(runner: Arc<dyn CommandRunner>) {
+    *RUNNER.write().unwrap_or_else(PoisonError::into_inner) = Some(runner);
+}
+
+/// Goes back to running commands on this host.
+pub fn reset_runner() {
+    *RUNNER.write().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+#[must_use]
+pub fn get_runner() -> Arc<dyn CommandRunner> {
+    match RUNNER
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+    {
+        Some(runner) => Arc::clone(runner),
+        None => Arc::new(LocalRunner::default()),
+    }
+}
+
+/// Runs `program` with the current runner.
+///
+/// # Errors
+///
+/// Will return `Err` if the program could not be started or did not exit in time
+pub fn run(
+    program: &str,
+    arguments: &[&str],
+    timeout: Option<Duration>,
+) -> io::Result<CommandOutput> {
+    let arguments = arguments
+        .iter()
+        .map(|a| (*a).to_string())
+        .collect::<Vec<String>>();
+    get_runner().run(program, &arguments, timeout)
+}
+
+fn quote(argument: &str) -> String {
+    if !argument.is_empty()
+        && argument
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,@%+".contains(c))
+    {
+        return String::from(argument);
+    }
+    format!("'{}'", argument.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| (*v).to_string()).collect()
+    }
+
+    #[test]
+    fn ssh_arguments_are_quoted_for_the_remote_shell() {
+        let mut runner = SshRunner::new("bastion");
+        runner.user = Some(String::from("pi"));
+        let arguments = runner.get_arguments(
+            "ipmitool",
+            &strings(&["-P", "pass word", "it's", "", "-H=10.0.0.1"]),
+        );
+        assert_eq!(
+            arguments,
+            strings(&[
+                "-o",
+                "BatchMode=yes",
+                "-p",
+                "22",
+                "pi@bastion",
+                "--",
+                "ipmitool -P 'pass word' 'it'\\''s' '' -H=10.0.0.1",
+            ])
+        );
+    }
+
+    #[test]
+    fn runners_are_parsed() {
+        assert_eq!(parse("local").unwrap().get_name(), "local");
+        assert_eq!(parse(" sudo ").unwrap().get_name(), "sudo -n");
+        assert_eq!(
+            parse("ssh://admin@jump.example.org:2222")
+                .unwrap()
+                .get_name(),
+            "ssh://admin@jump.example.org:2222"
+        );
+        assert_eq!(parse("ssh://jump").unwrap().get_name(), "ssh://jump:22");
+        assert!(parse("telnet://jump").is_err());
+        assert!(parse("ssh://jump:port").is_err());
+        assert!(parse("ssh://admin@").is_err());
+    }
+
+    #[test]
+    fn recording_runner_answers_with_the_set_output() {
+        let runner = RecordingRunner::new().with_output("false", CommandOutput::new(1, ""));
+        assert!(runner
+            .run("true", &strings(&["a"]), None)
+            .unwrap()
+            .is_success());
+        assert!(!runner.run("false", &[], None).unwrap().is_success());
+        assert_eq!(
+            runner.get_calls(),
+            vec![strings(&["true", "a"]), strings(&["false"])]
+        );
+    }
+}
@@ -0,0 +1,228 @@
+use std::fmt;
+use std::fs;
+use std::io;
+
+use crate::utils;
+
+const GLOBAL_SECTION: &str = "all";
+const MULTI_VALUED_KEYS: [&str; 3] = ["dtoverlay", "dtparam", "initramfs"];
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+enum Line {
+    Section(String),
+    Setting(String, String),
+    Other(String),
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub struct ConfigTxt {
+    lines: Vec<Line>,
+}
+
+impl ConfigTxt {
+    #[must_use]
+    pub fn new() -> Self {
+        ConfigTxt { lines: Vec::new() }
+    }
+
+    #[must_use]
+    pub fn parse(content: &str) -> Self {
+        let lines = content
+            .lines()
+            .map(|line| {
+                let trimmed = line.trim();
+                if trimmed.starts_with('[') && trimmed.ends_with(']') && trimmed.len() > 2 {
+                    return Line::Section(trimmed[1..trimmed.len() - 1].to_string());
+                }
+                if !trimmed.starts_with('#') {
+                    if let Some((key, value)) = trimmed.split_once('=') {
+                        if !key.is_empty() && !key.contains(char::is_whitespace) {
+                            return Line::Setting(key.to_string(), value.to_string());
+                        }
+                    }
+                }
+                Line::Other(line.to_string())
+            })
+            .collect::<Vec<Line>>();
+        ConfigTxt { lines }
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if `filename` could not be read
+    pub fn read(filename: &str) -> io::Result<Self> {
+        Ok(ConfigTxt::parse(&fs::read_to_string(filename)?))
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if `filename` could not be written to
+    pub fn write(&self, filename: &str) -> io::Result<()> {
+        utils::write_file(filename, &self.to_string())
+    }
+
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.get_in(GLOBAL_SECTION, key)
+    }
+
+    #[must_use]
+    pub fn get_in(&self, section: &str, key: &str) -> Option<String> {
+        self.get_all_in(section, key).pop()
+    }
+
+    #[must_use]
+    pub fn get_all(&self, key: &str) -> Vec<String> {
+        self.get_all_in(GLOBAL_SECTION, key)
+    }
+
+    #[must_use]
+    pub fn get_all_in(&self, section: &str, key: &str) -> Vec<String> {
+        self.settings_in(section)
+            .into_iter()
+            .filter_map(|index| match &self.lines[index] {
+                Line::Setting(k, v) if k.eq(key) => Some(v.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) {
+        self.set_in(GLOBAL_SECTION, key, value);
+    }
+
+    /// Replaces every occurrence of `key` in `section` with a single `key=value` line.
+    pub fn set_in(&mut self, section: &str, key: &str, value: &str) {
+        let indices = self.indices_of(section, key);
+        match indices.split_first() {
+            Some((first, rest)) => {
+                self.lines[*first] = Line::Setting(key.to_string(), value.to_string());
+                for index in rest.iter().rev() {
+                    self.lines.remove(*index);
+                }
+            }
+            None => self.append_to(section, key, value),
+        }
+    }
+
+    /// Adds `key=value` to `section` unless that exact setting is already present.
+    pub fn add_in(&mut self, section: &str, key: &str, value: &str) {
+        if !self.get_all_in(section, key).iter().any(|v| v.eq(value)) {
+            self.append_to(section, key, value);
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.remove_in(GLOBAL_SECTION, key);
+    }
+
+    pub fn remove_in(&mut self, section: &str, key: &str) {
+        for index in self.indices_of(section, key).iter().rev() {
+            self.lines.remove(*index);
+        }
+    }
+
+    pub fn remove_value_in(&mut self, section: &str, key: &str, value: &str) {
+        let indices = self
+            .indices_of(section, key)
+            .into_iter()
+            .filter(|i| matches!(&self.lines[*i], Line::Setting(_, v) if v.eq(value)))
+            .collect::<Vec<usize>>();
+        for index in indices.iter().rev() {
+            self.lines.remove(*index);
+        }
+    }
+
+    pub fn set_arm_64bit(&mut self, enabled: bool) {
+        self.set("arm_64bit", if enabled { "1" } else { "0" });
+    }
+
+    pub fn set_kernel(&mut self, kernel: &str) {
+        self.set("kernel", kernel);
+    }
+
+    pub fn add_dtoverlay(&mut self, overlay: &str) {
+        self.add_in(GLOBAL_SECTION, "dtoverlay", overlay);
+    }
+
+    pub fn remove_dtoverlay(&mut self, overlay: &str) {
+        self.remove_value_in(GLOBAL_SECTION, "dtoverlay", overlay);
+    }
+
+    /// Applies the settings of `other` on top of this file. Keys that may appear several
+    /// times, such as `dtoverlay`, are added, all other keys are replaced.
+    pub fn merge(&mut self, other: &ConfigTxt) {
+        let mut section = String::from(GLOBAL_SECTION);
+        for line in &other.lines {
+            match line {
+                Line::Section(name) => section.clone_from(name),
+                Line::Setting(key, value) => {
+                    if MULTI_VALUED_KEYS.contains(&key.as_str()) {
+                        self.add_in(&section, key, value);
+                    } else {
+                        self.set_in(&section, key, value);
+                    }
+                }
+                Line::Other(_) => {}
+            }
+        }
+    }
+
+    fn settings_in(&self, section: &str) -> Vec<usize> {
+        let mut current = GLOBAL_SECTION;
+        let mut indices = Vec::new();
+        for (index, line) in self.lines.iter().enumerate() {
+            match line {
+                Line::Section(name) => current = name.as_str(),
+                Line::Setting(_, _) if current.eq(section) => indices.push(index),
+                _ => {}
+            }
+        }
+        indices
+    }
+
+    fn indices_of(&self, section: &str, key: &str) -> Vec<usize> {
+        self.settings_in(section)
+            .into_iter()
+            .filter(|i| matches!(&self.lines[*i], Line::Setting(k, _) if k.eq(key)))
+            .collect()
+    }
+
+    fn append_to(&mut self, section: &str, key: &str, value: &str) {
+        let setting = Line::Setting(key.to_string(), value.to_string());
+        let mut current = GLOBAL_SECTION;
+        let mut position = None;
+        for (index, line) in self.lines.iter().enumerate() {
+            match line {
+                Line::Section(name) => current = name.as_str(),
+                Line::Setting(_, _) if current.eq(section) => position = Some(index + 1),
+                _ => {}
+            }
+        }
+        if let Some(index) = position {
+            self.lines.insert(index, setting);
+            return;
+        }
+        let last_section = self.lines.iter().rev().find_map(|l| match l {
+            Line::Section(name) => Some(name.as_str()),
+            _ => None,
+        });
+        if !last_section.unwrap_or(GLOBAL_SECTION).eq(section) {
+            self.lines.push(Line::Section(section.to_string()));
+        }
+        self.lines.push(setting);
+    }
+}
+
+impl fmt::Display for ConfigTxt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            match line {
+                Line::Section(name) => writeln!(f, "[{}]", name)?,
+                Line::Setting(key, value) => writeln!(f, "{}={}", key, value)?,
+                Line::Other(raw) => writeln!(f, "{}", raw)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -18,6 +18,12 @@ pub struct Configuration {
     pub pxe: bool,
     pub pxe_kernel: String,
     pub pxe_options: String,
+    #[serde(default)]
+    pub diskless: bool,
+    #[serde(default)]
+    pub nfs_server: String,
+    #[serde(default)]
+    pub nfs_root: String,
 }
 
 impl Configuration {
@@ -71,6 +77,15 @@ impl Configuration {
                 self.pxe_options = other.pxe_options;
             }
         }
+        self.diskless = other.diskless;
+        if self.diskless {
+            if self.nfs_server.is_empty() {
+                self.nfs_server = other.nfs_server;
+            }
+            if self.nfs_root.is_empty() {
+                self.nfs_root = other.nfs_root;
+            }
+        }
     }
 }
 
@@ -87,6 +102,9 @@ impl Default for Configuration {
             pxe: false,
             pxe_kernel: String::new(),
             pxe_options: String::new(),
+            diskless: false,
+            nfs_server: String::new(),
+            nfs_root: String::new(),
         }
     }
 }
@@ -3,9 +3,27 @@ use rusqlite::Row;
 use serde::{Deserialize, Serialize};
 use yaml_rust::Yaml;
 
+use crate::reservation::{ReservationConflict, Reservations};
+use crate::service;
 use crate::service::Service;
 use crate::task::Task;
 use crate::task::Type::StopIfTrue;
+use crate::yaml_schema;
+use crate::yaml_schema::{Field, Kind, SchemaError};
+
+const LOG_SCHEMA: Kind = Kind::Mapping(&[
+    Field::required("message", Kind::String),
+    Field::optional("occurrence", Kind::Integer),
+]);
+
+/// The keys a deployment file may have.
+pub const SCHEMA: Kind = Kind::Mapping(&[
+    Field::required("services", Kind::MapOf(&service::SCHEMA)),
+    Field::optional(
+        "stop",
+        Kind::Mapping(&[Field::optional("log", Kind::SequenceOf(&LOG_SCHEMA))]),
+    ),
+]);
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Deployment {
@@ -32,6 +50,19 @@ impl Deployment {
         }
     }
 
+    /// Reads a deployment from the content of a deployment file after checking it against
+    /// [`SCHEMA`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` with every problem of the file
+    pub fn parse(name: &str, content: &str) -> Result<Self, Vec<SchemaError>> {
+        let yaml = yaml_schema::validate(content, &SCHEMA)?;
+        Ok(Deployment::from_yaml(name, &yaml))
+    }
+
+    /// Reads a deployment from YAML that has been checked against [`SCHEMA`]; anything else
+    /// is ignored.
     #[must_use]
     pub fn from_yaml(name: &str, yaml: &Yaml) -> Self {
         let mut services: Vec<Service> = Vec::new();
@@ -95,4 +126,31 @@ impl Deployment {
     pub fn get_services(&self) -> Vec<Service> {
         self.services.clone()
     }
+
+    /// Returns the nodes the services are running on or pinned to.
+    #[must_use]
+    pub fn get_nodes(&self) -> Vec<String> {
+        let mut nodes = self
+            .services
+            .iter()
+            .filter_map(|s| s.node.clone().or_else(|| s.preferred_node.clone()))
+            .collect::<Vec<String>>();
+        nodes.sort();
+        nodes.dedup();
+        nodes
+    }
+
+    /// Checks that no one but `owner`, the user deploying, holds a reservation on the nodes of
+    /// this deployment. The stored `owner` of a deployment is not yet the actual user.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` with every reservation of another owner on one of the nodes
+    pub fn check_reservations(
+        &self,
+        owner: &str,
+        reservations: &Reservations,
+    ) -> Result<(), Vec<ReservationConflict>> {
+        reservations.check_claim(owner, &self.get_nodes(), Utc::now().naive_local())
+    }
 }
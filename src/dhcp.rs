@@ -0,0 +1,152 @@
+use std::io;
+use std::net::Ipv4Addr;
+
+use string_builder::Builder;
+
+use crate::architecture::Architecture;
+use crate::mac_address::MacAddress;
+use crate::node::Node;
+use crate::utils;
+
+const BEGIN_MARKER: &str = "# BEGIN common_code host reservations";
+const END_MARKER: &str = "# END common_code host reservations";
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Format {
+    Dnsmasq,
+    IscDhcpd,
+}
+
+#[derive(Debug, Clone)]
+pub struct DhcpOptions {
+    pub next_server: String,
+    pub arm32_boot_filename: String,
+    pub arm64_boot_filename: String,
+    pub x86_boot_filename: String,
+}
+
+impl DhcpOptions {
+    #[must_use]
+    pub fn new(next_server: &str) -> Self {
+        DhcpOptions {
+            next_server: String::from(next_server),
+            arm32_boot_filename: String::from("bootcode.bin"),
+            arm64_boot_filename: String::from("bootcode.bin"),
+            x86_boot_filename: String::from("pxelinux.0"),
+        }
+    }
+
+    #[must_use]
+    pub fn get_boot_filename(&self, architecture: &Architecture) -> &str {
+        match architecture {
+            Architecture::ARM32 => self.arm32_boot_filename.as_str(),
+            Architecture::ARM64 => self.arm64_boot_filename.as_str(),
+            Architecture::X86 => self.x86_boot_filename.as_str(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HostReservation {
+    pub id: String,
+    pub mac_address: MacAddress,
+    pub ipv4_address: Ipv4Addr,
+    pub hostname: String,
+    pub next_server: String,
+    pub boot_filename: String,
+}
+
+impl HostReservation {
+    #[must_use]
+    pub fn from_node(node: &Node, options: &DhcpOptions) -> Self {
+        HostReservation {
+            id: node.id.clone(),
+            mac_address: node.mac_address,
+            ipv4_address: node.ipv4_address,
+            hostname: node.id.clone(),
+            next_server: node
+                .tftp_server
+                .clone()
+                .unwrap_or_else(|| options.next_server.clone()),
+            boot_filename: String::from(options.get_boot_filename(&node.architecture)),
+        }
+    }
+
+    #[must_use]
+    pub fn to_dnsmasq(&self) -> String {
+        let tag = format!("node-{}", self.id);
+        format!(
+            "dhcp-host={},set:{},{},{}\ndhcp-boot=tag:{},{},,{}\n",
+            self.mac_address,
+            tag,
+            self.ipv4_address,
+            self.hostname,
+            tag,
+            self.boot_filename,
+            self.next_server
+        )
+    }
+
+    #[must_use]
+    pub fn to_isc_dhcpd(&self) -> String {
+        let mut builder = Builder::default();
+        builder.append(format!("host {} {{\n", self.id));
+        utils::ident_and_append(
+            &mut builder,
+            &format!("hardware ethernet {};\n", self.mac_address),
+            2,
+        );
+        utils::ident_and_append(
+            &mut builder,
+            &format!("fixed-address {};\n", self.ipv4_address),
+            2,
+        );
+        utils::ident_and_append(
+            &mut builder,
+            &format!("option host-name {};\n", utils::quote(&self.hostname)),
+            2,
+        );
+        utils::ident_and_append(
+            &mut builder,
+            &format!("next-server {};\n", self.next_server),
+            2,
+        );
+        utils::ident_and_append(
+            &mut builder,
+            &format!("filename {};\n", utils::quote(&self.boot_filename)),
+            2,
+        );
+        builder.append("}\n");
+        builder.string().unwrap_or_default()
+    }
+}
+
+#[must_use]
+pub fn generate(nodes: &[Node], options: &DhcpOptions, format: Format) -> String {
+    let mut reservations = nodes
+        .iter()
+        .map(|n| HostReservation::from_node(n, options))
+        .collect::<Vec<HostReservation>>();
+    reservations.sort_by(|a, b| a.id.cmp(&b.id));
+    let mut builder = Builder::default();
+    builder.append(BEGIN_MARKER);
+    builder.append("\n");
+    for reservation in &reservations {
+        match format {
+            Format::Dnsmasq => builder.append(reservation.to_dnsmasq()),
+            Format::IscDhcpd => builder.append(reservation.to_isc_dhcpd()),
+        }
+    }
+    builder.append(END_MARKER);
+    builder.append("\n");
+    builder.string().unwrap_or_default()
+}
+
+/// Replaces the managed reservation block in `filename`, or appends it if there is none yet.
+///
+/// # Errors
+///
+/// Will return `Err` if `filename` could not be read or written
+pub fn update_file(filename: &str, fragment: &str) -> io::Result<bool> {
+    utils::replace_block_in_file(filename, BEGIN_MARKER, END_MARKER, fragment)
+}
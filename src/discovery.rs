@@ -0,0 +1,308 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::Ipv4Addr;
+
+use crate::architecture::Architecture;
+use crate::capacity::Capacity;
+use crate::mac_address::MacAddress;
+use crate::node::Node;
+use crate::serial_number::SerialNumber;
+
+/// Organizationally unique identifiers of Raspberry Pi network interfaces.
+const RASPBERRY_PI_OUIS: [[u8; 3]; 6] = [
+    [0xb8, 0x27, 0xeb],
+    [0xdc, 0xa6, 0x32],
+    [0xe4, 0x5f, 0x01],
+    [0xd8, 0x3a, 0xdd],
+    [0x28, 0xcd, 0xc1],
+    [0x2c, 0xcf, 0x67],
+];
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Source {
+    Dnsmasq,
+    IscDhcpd,
+    Neighbour,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Dnsmasq => write!(f, "dnsmasq lease"),
+            Source::IscDhcpd => write!(f, "dhcpd lease"),
+            Source::Neighbour => write!(f, "neighbour table"),
+        }
+    }
+}
+
+/// A MAC address seen on the network, with whatever else the source knew about it.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Sighting {
+    pub mac_address: MacAddress,
+    pub ipv4_address: Option<Ipv4Addr>,
+    pub hostname: Option<String>,
+    pub architecture: Option<Architecture>,
+    pub source: Source,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DiscoveredNode {
+    pub mac_address: MacAddress,
+    pub ipv4_address: Option<Ipv4Addr>,
+    pub hostname: Option<String>,
+    pub architecture: Option<Architecture>,
+    pub sources: Vec<Source>,
+    pub suggested_id: String,
+    pub suggested_tftp_prefix: String,
+}
+
+impl DiscoveredNode {
+    #[must_use]
+    pub fn is_raspberry_pi(&self) -> bool {
+        let octets = self.mac_address.get_octets();
+        RASPBERRY_PI_OUIS.iter().any(|oui| oui[..] == octets[..3])
+    }
+
+    /// Turns the discovered node into an inventory entry once its serial number is known, as
+    /// it can not be discovered on the network. A Raspberry Pi requests its boot files by
+    /// serial number, which therefore becomes its TFTP prefix.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if no IPv4 address was seen for the node
+    pub fn into_node(self, serial_number: SerialNumber) -> Result<Node, String> {
+        let ipv4_address = self.ipv4_address.ok_or_else(|| {
+            format!(
+                "no IPv4 address was seen for {}, add it by hand",
+                self.mac_address
+            )
+        })?;
+        let architecture = self.architecture.clone().unwrap_or(Architecture::ARM64);
+        let tftp_prefix = if self.is_raspberry_pi() {
+            serial_number.to_string()
+        } else {
+            self.suggested_tftp_prefix
+        };
+        Ok(Node {
+            id: self.suggested_id,
+            name: self.hostname.unwrap_or_else(|| String::from("node")),
+            tftp_prefix,
+            mac_address: self.mac_address,
+            serial_number,
+            ipv4_address,
+            log_inputs: Vec::new(),
+            architecture,
+            pxe: false,
+            labels: BTreeMap::new(),
+            capacity: Capacity::default(),
+            extra: BTreeMap::new(),
+            hardware: None,
+            maintenance: None,
+            groups: Vec::new(),
+            tftp_server: None,
+            power: None,
+            inherited: Vec::new(),
+        })
+    }
+}
+
+impl fmt::Display for DiscoveredNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}", self.suggested_id, self.mac_address)?;
+        if let Some(ipv4_address) = self.ipv4_address {
+            write!(f, ", {}", ipv4_address)?;
+        }
+        if let Some(hostname) = &self.hostname {
+            write!(f, ", {}", hostname)?;
+        }
+        let sources = self
+            .sources
+            .iter()
+            .map(Source::to_string)
+            .collect::<Vec<String>>();
+        write!(f, ") seen in {}", sources.join(", "))
+    }
+}
+
+/// Parses a dnsmasq lease file with lines of `<expiry> <mac> <ip> <hostname> <client-id>`.
+#[must_use]
+pub fn parse_dnsmasq_leases(content: &str) -> Vec<Sighting> {
+    let mut sightings = Vec::new();
+    for line in content.lines() {
+        let fields = line.split_whitespace().collect::<Vec<&str>>();
+        if fields.len() < 4 {
+            continue;
+        }
+        let mac_address = match MacAddress::parse(fields[1]) {
+            Ok(mac_address) => mac_address,
+            Err(_) => continue,
+        };
+        let ipv4_address = match fields[2].parse::<Ipv4Addr>() {
+            Ok(ipv4_address) => ipv4_address,
+            Err(_) => continue,
+        };
+        sightings.push(Sighting {
+            mac_address,
+            ipv4_address: Some(ipv4_address),
+            hostname: Some(fields[3].to_string()).filter(|h| h != "*"),
+            architecture: None,
+            source: Source::Dnsmasq,
+        });
+    }
+    sightings
+}
+
+/// Parses the `lease` blocks of an ISC dhcpd lease file, skipping those not in binding state
+/// `active`. The PXE client architecture is taken from the vendor class identifier if
+/// present.
+#[must_use]
+pub fn parse_isc_dhcpd_leases(content: &str) -> Vec<Sighting> {
+    let mut sightings = Vec::new();
+    let mut ipv4_address = None;
+    let mut mac_address = None;
+    let mut hostname = None;
+    let mut architecture = None;
+    let mut active = false;
+    for line in content.lines() {
+        let line = line.trim().trim_end_matches(';');
+        if let Some(rest) = line.strip_prefix("lease ") {
+            ipv4_address = rest.trim_end_matches('{').trim().parse::<Ipv4Addr>().ok();
+            mac_address = None;
+            hostname = None;
+            architecture = None;
+            active = false;
+        } else if let Some(rest) = line.strip_prefix("binding state ") {
+            active = rest.trim() == "active";
+        } else if let Some(rest) = line.strip_prefix("hardware ethernet ") {
+            mac_address = MacAddress::parse(rest).ok();
+        } else if let Some(rest) = line.strip_prefix("client-hostname ") {
+            hostname = Some(rest.trim_matches('"').to_string()).filter(|h| !h.is_empty());
+        } else if let Some(rest) = line.strip_prefix("set vendor-class-identifier = ") {
+            architecture = get_pxe_architecture(rest.trim_matches('"'));
+        } else if line == "}" {
+            if let (true, Some(ip), Some(mac)) = (active, ipv4_address.take(), mac_address.take()) {
+                sightings.push(Sighting {
+                    mac_address: mac,
+                    ipv4_address: Some(ip),
+                    hostname: hostname.take(),
+                    architecture: architecture.take(),
+                    source: Source::IscDhcpd,
+                });
+            }
+        }
+    }
+    sightings
+}
+
+/// Parses the output of `ip neigh`, skipping IPv6 and incomplete entries.
+#[must_use]
+pub fn parse_ip_neigh(content: &str) -> Vec<Sighting> {
+    let mut sightings = Vec::new();
+    for line in content.lines() {
+        let fields = line.split_whitespace().collect::<Vec<&str>>();
+        let ipv4_address = match fields.first().map(|f| f.parse::<Ipv4Addr>()) {
+            Some(Ok(ipv4_address)) => ipv4_address,
+            _ => continue,
+        };
+        let lladdr = fields
+            .iter()
+            .position(|f| *f == "lladdr")
+            .and_then(|i| fields.get(i + 1))
+            .and_then(|mac| MacAddress::parse(mac).ok());
+        if let Some(mac_address) = lladdr {
+            sightings.push(Sighting {
+                mac_address,
+                ipv4_address: Some(ipv4_address),
+                hostname: None,
+                architecture: None,
+                source: Source::Neighbour,
+            });
+        }
+    }
+    sightings
+}
+
+/// Groups `sightings` by MAC address and returns those not registered in `nodes`, with ids
+/// that are unique across the inventory. Leases take precedence over the neighbour table.
+#[must_use]
+pub fn discover(nodes: &[Node], sightings: &[Sighting]) -> Vec<DiscoveredNode> {
+    let mut discovered: BTreeMap<MacAddress, DiscoveredNode> = BTreeMap::new();
+    for sighting in sightings {
+        if nodes
+            .iter()
+            .any(|n| n.mac_address.eq(&sighting.mac_address))
+        {
+            continue;
+        }
+        let entry = discovered
+            .entry(sighting.mac_address)
+            .or_insert_with(|| DiscoveredNode {
+                mac_address: sighting.mac_address,
+                ipv4_address: None,
+                hostname: None,
+                architecture: None,
+                sources: Vec::new(),
+                suggested_id: String::new(),
+                suggested_tftp_prefix: sighting.mac_address.to_dashed(),
+            });
+        let is_lease = !sighting.source.eq(&Source::Neighbour);
+        if entry.ipv4_address.is_none() || is_lease {
+            entry.ipv4_address = sighting.ipv4_address.or(entry.ipv4_address);
+        }
+        if sighting.hostname.is_some() {
+            entry.hostname.clone_from(&sighting.hostname);
+        }
+        if sighting.architecture.is_some() {
+            entry.architecture.clone_from(&sighting.architecture);
+        }
+        if !entry.sources.contains(&sighting.source) {
+            entry.sources.push(sighting.source);
+        }
+    }
+    let mut ids = nodes.iter().map(|n| n.id.clone()).collect::<Vec<String>>();
+    let mut discovered = discovered.into_values().collect::<Vec<DiscoveredNode>>();
+    for node in &mut discovered {
+        if node.architecture.is_none() && node.is_raspberry_pi() {
+            node.architecture = Some(Architecture::ARM64);
+        }
+        let octets = node.mac_address.get_octets();
+        let base = node
+            .hostname
+            .as_ref()
+            .filter(|h| is_valid_id(h))
+            .cloned()
+            .unwrap_or_else(|| format!("node-{:02x}{:02x}{:02x}", octets[3], octets[4], octets[5]));
+        let mut id = base.clone();
+        let mut suffix = 2;
+        while ids.contains(&id) {
+            id = format!("{}-{}", base, suffix);
+            suffix += 1;
+        }
+        ids.push(id.clone());
+        node.suggested_id = id;
+    }
+    discovered
+}
+
+/// Maps the client architecture of a `PXEClient:Arch:xxxxx` vendor class (RFC 4578).
+fn get_pxe_architecture(vendor_class: &str) -> Option<Architecture> {
+    let code = vendor_class
+        .strip_prefix("PXEClient:Arch:")?
+        .split(':')
+        .next()?
+        .parse::<u16>()
+        .ok()?;
+    match code {
+        0 | 6 | 7 | 9 => Some(Architecture::X86),
+        10 => Some(Architecture::ARM32),
+        11 => Some(Architecture::ARM64),
+        _ => None,
+    }
+}
+
+fn is_valid_id(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
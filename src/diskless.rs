@@ -0,0 +1,234 @@
+use std::fs;
+use std::io;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use string_builder::Builder;
+
+use crate::cmdline_txt::CmdlineTxt;
+use crate::command_runner::{CommandRunner, LocalRunner};
+use crate::configuration::Configuration;
+use crate::dry_run;
+use crate::dry_run::Effect;
+use crate::node::Node;
+use crate::utils;
+
+const BEGIN_MARKER: &str = "# BEGIN common_code diskless roots";
+const END_MARKER: &str = "# END common_code diskless roots";
+const EXPORT_OPTIONS: &str = "rw,sync,no_subtree_check,no_root_squash";
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Overlay {
+    pub lower: String,
+    pub upper: String,
+    pub work: String,
+    pub merged: String,
+}
+
+impl Overlay {
+    #[must_use]
+    pub fn get_mount_options(&self) -> String {
+        format!(
+            "lowerdir={},upperdir={},workdir={},index=on,nfs_export=on",
+            self.lower, self.upper, self.work
+        )
+    }
+
+    #[must_use]
+    pub fn is_mounted(&self) -> bool {
+        match fs::read_to_string("/proc/mounts") {
+            Ok(mounts) => mounts
+                .lines()
+                .any(|l| l.split_whitespace().nth(1) == Some(self.merged.as_str())),
+            Err(_) => false,
+        }
+    }
+
+    /// Mounts the overlay on this host unless it is mounted already, and returns whether it
+    /// is. In dry-run the mount is recorded and reported as successful instead.
+    #[must_use]
+    pub fn mount(&self) -> bool {
+        self.is_mounted() || self.mount_with(&LocalRunner::default())
+    }
+
+    /// Mounts the overlay with `runner`, whether or not it is mounted already.
+    #[must_use]
+    pub fn mount_with(&self, runner: &dyn CommandRunner) -> bool {
+        let options = self.get_mount_options();
+        let arguments = ["-t", "overlay", "overlay", "-o", &options, &self.merged];
+        if dry_run::record(Effect::Command {
+            program: String::from("mount"),
+            arguments: arguments.iter().map(ToString::to_string).collect(),
+        }) {
+            return true;
+        }
+        let arguments = arguments
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<String>>();
+        match runner.run("mount", &arguments, None) {
+            Ok(output) => output.is_success(),
+            Err(_) => false,
+        }
+    }
+}
+
+#[must_use]
+pub fn get_root_directory(configuration: &Configuration, node: &Node) -> String {
+    format!(
+        "{}/{}",
+        configuration.nfs_root.trim_end_matches('/'),
+        node.id
+    )
+}
+
+/// Returns the kernel parameters for booting `node` from its NFS root; merge them into the
+/// command line of the image.
+///
+/// # Errors
+///
+/// Will return `Err` if `configuration` is not set up for diskless boot
+pub fn get_kernel_cmdline(
+    configuration: &Configuration,
+    node: &Node,
+) -> Result<CmdlineTxt, String> {
+    check(configuration)?;
+    let mut cmdline = CmdlineTxt::parse(&configuration.pxe_options);
+    cmdline.set("root", "/dev/nfs");
+    cmdline.set(
+        "nfsroot",
+        &format!(
+            "{}:{},vers=3,tcp",
+            configuration.nfs_server,
+            get_root_directory(configuration, node)
+        ),
+    );
+    cmdline.set("ip", "dhcp");
+    cmdline.remove("rootfstype");
+    cmdline.set_flag("rw");
+    cmdline.set_flag("rootwait");
+    Ok(cmdline)
+}
+
+/// # Errors
+///
+/// Will return `Err` if `configuration` is not set up for diskless boot
+pub fn get_exports_entry(configuration: &Configuration, node: &Node) -> Result<String, String> {
+    check(configuration)?;
+    Ok(format!(
+        "{} {}({})",
+        get_root_directory(configuration, node),
+        node.ipv4_address,
+        EXPORT_OPTIONS
+    ))
+}
+
+/// # Errors
+///
+/// Will return `Err` if `configuration` is not set up for diskless boot
+pub fn get_exports(configuration: &Configuration, nodes: &[Node]) -> Result<String, String> {
+    let mut builder = Builder::default();
+    builder.append(BEGIN_MARKER);
+    builder.append("\n");
+    for node in nodes {
+        builder.append(get_exports_entry(configuration, node)?);
+        builder.append("\n");
+    }
+    builder.append(END_MARKER);
+    builder.append("\n");
+    Ok(builder.string().unwrap_or_default())
+}
+
+/// Replaces the managed block in an exports file such as `/etc/exports`.
+///
+/// # Errors
+///
+/// Will return `Err` if `filename` could not be read or written
+pub fn update_exports(filename: &str, exports: &str) -> io::Result<bool> {
+    utils::replace_block_in_file(filename, BEGIN_MARKER, END_MARKER, exports)
+}
+
+/// Creates the directories of a copy-on-write root for `node` on top of `image_root`, the
+/// extracted root filesystem of a built image.
+///
+/// # Errors
+///
+/// Will return `Err` if `configuration` is not set up for diskless boot or the directories
+/// could not be created
+pub fn prepare_overlay(
+    configuration: &Configuration,
+    image_root: &str,
+    node: &Node,
+) -> io::Result<Overlay> {
+    check(configuration).map_err(|e| io::Error::new(ErrorKind::InvalidInput, e))?;
+    if !Path::new(image_root).is_dir() {
+        return Err(io::Error::new(
+            ErrorKind::NotFound,
+            format!("image root {} is not a directory", image_root),
+        ));
+    }
+    let state = format!(
+        "{}/.overlay/{}",
+        configuration.nfs_root.trim_end_matches('/'),
+        node.id
+    );
+    let overlay = Overlay {
+        lower: image_root.to_string(),
+        upper: format!("{}/upper", state),
+        work: format!("{}/work", state),
+        merged: get_root_directory(configuration, node),
+    };
+    for directory in &[&overlay.upper, &overlay.work, &overlay.merged] {
+        utils::create_directory(Path::new(directory))?;
+    }
+    Ok(overlay)
+}
+
+fn check(configuration: &Configuration) -> Result<(), String> {
+    if !configuration.diskless {
+        return Err(format!(
+            "{} is not a diskless configuration",
+            configuration.name
+        ));
+    }
+    if configuration.nfs_server.is_empty() || configuration.nfs_root.is_empty() {
+        return Err(format!(
+            "{} has no NFS server or root directory",
+            configuration.name
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command_runner::{CommandOutput, RecordingRunner};
+
+    #[test]
+    fn mount_runs_with_the_runner() {
+        let overlay = Overlay {
+            lower: String::from("/srv/image"),
+            upper: String::from("/srv/n1/upper"),
+            work: String::from("/srv/n1/work"),
+            merged: String::from("/srv/n1/root"),
+        };
+        let runner = RecordingRunner::new();
+        assert!(overlay.mount_with(&runner));
+        assert_eq!(
+            runner.get_calls(),
+            vec![vec![
+                "mount",
+                "-t",
+                "overlay",
+                "overlay",
+                "-o",
+                "lowerdir=/srv/image,upperdir=/srv/n1/upper,workdir=/srv/n1/work,index=on,\
+                 nfs_export=on",
+                "/srv/n1/root",
+            ]]
+        );
+        let runner = RecordingRunner::new().with_output("mount", CommandOutput::new(32, ""));
+        assert!(!overlay.mount_with(&runner));
+    }
+}
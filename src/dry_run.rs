@@ -0,0 +1,163 @@
+use std::fmt;
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+
+/// The effects recorded while dry-run is enabled; `None` while it is not.
+static EFFECTS: Mutex<Option<Recording>> = Mutex::new(None);
+
+/// The effects recorded so far and where each nested [`enable`] started, outermost first.
+#[derive(Debug)]
+struct Recording {
+    starts: Vec<usize>,
+    effects: Vec<Effect>,
+}
+
+/// Something that would have changed hardware or files had dry-run not been enabled.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Effect {
+    Command {
+        program: String,
+        arguments: Vec<String>,
+    },
+    Power {
+        action: String,
+        target: String,
+    },
+    WriteFile {
+        filename: String,
+        content: String,
+    },
+    EditFile {
+        filename: String,
+        change: String,
+    },
+    CopyFile {
+        source: String,
+        destination: String,
+    },
+    CreateDirectory {
+        directory: String,
+    },
+}
+
+impl fmt::Display for Effect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Effect::Command { program, arguments } => {
+                write!(f, "run {}", program)?;
+                for argument in arguments {
+                    write!(f, " {}", argument)?;
+                }
+                Ok(())
+            }
+            Effect::Power { action, target } => write!(f, "power {} {}", action, target),
+            Effect::WriteFile { filename, content } => {
+                write!(f, "write {} ({} bytes)", filename, content.len())
+            }
+            Effect::EditFile { filename, change } => write!(f, "edit {}: {}", filename, change),
+            Effect::CopyFile {
+                source,
+                destination,
+            } => write!(f, "copy {} to {}", source, destination),
+            Effect::CreateDirectory { directory } => write!(f, "create directory {}", directory),
+        }
+    }
+}
+
+/// Starts recording effects instead of performing them. Enabling it again while enabled
+/// nests: effects recorded so far are kept until the outermost [`disable`].
+pub fn enable() {
+    let mut recording = EFFECTS.lock().unwrap_or_else(PoisonError::into_inner);
+    match recording.as_mut() {
+        Some(recording) => recording.starts.push(recording.effects.len()),
+        None => {
+            *recording = Some(Recording {
+                starts: vec![0],
+                effects: Vec::new(),
+            });
+        }
+    }
+}
+
+/// Stops recording and returns the effects recorded since the matching [`enable`]. Dry-run
+/// stays enabled until every `enable` has been matched.
+pub fn disable() -> Vec<Effect> {
+    let mut recording = EFFECTS.lock().unwrap_or_else(PoisonError::into_inner);
+    let effects = match recording.as_mut() {
+        Some(current) => {
+            let start = current.starts.pop().unwrap_or_default();
+            if current.starts.is_empty() {
+                recording.take().map(|r| r.effects).unwrap_or_default()
+            } else {
+                current.effects[start..].to_vec()
+            }
+        }
+        None => Vec::new(),
+    };
+    effects
+}
+
+#[must_use]
+pub fn is_enabled() -> bool {
+    EFFECTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .is_some()
+}
+
+/// Returns the effects recorded since the innermost [`enable`] and keeps recording.
+pub fn take() -> Vec<Effect> {
+    match EFFECTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_mut()
+    {
+        Some(recording) => {
+            let start = recording.starts.last().copied().unwrap_or_default();
+            recording.effects.split_off(start)
+        }
+        None => Vec::new(),
+    }
+}
+
+/// Records `effect` if dry-run is enabled. Returns whether it was recorded, in which case the
+/// caller must not perform it.
+pub fn record(effect: Effect) -> bool {
+    match EFFECTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_mut()
+    {
+        Some(recording) => {
+            recording.effects.push(effect);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Runs `preview` with dry-run enabled and returns its result with the recorded effects.
+/// Dry-run is global, so effects of other threads running meanwhile are recorded as well. If
+/// `preview` panics, dry-run is disabled again before the panic goes on.
+pub fn run<T, F>(preview: F) -> (T, Vec<Effect>)
+where
+    F: FnOnce() -> T,
+{
+    enable();
+    let guard = DisableOnPanic;
+    let result = preview();
+    let effects = disable();
+    drop(guard);
+    (result, effects)
+}
+
+/// Disables dry-run when dropped during a panic.
+struct DisableOnPanic;
+
+impl Drop for DisableOnPanic {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            disable();
+        }
+    }
+}
@@ -0,0 +1,464 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::capacity;
+use crate::capacity::Capacity;
+use crate::serial_number::SerialNumber;
+
+/// Relative difference tolerated between claimed and reported sizes; the kernel reserves
+/// part of the memory and disks are sold in decimal units.
+const SIZE_TOLERANCE_PERCENT: u64 = 10;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+pub struct Disk {
+    pub name: String,
+    pub size_mb: u64,
+    pub disk_type: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, Eq, PartialEq)]
+pub struct HardwareProfile {
+    pub cpu_cores: Option<u32>,
+    pub cpu_model: Option<String>,
+    pub memory_mb: Option<u64>,
+    pub disks: Vec<Disk>,
+    pub board_model: Option<String>,
+    pub serial_number: Option<SerialNumber>,
+    pub revision: Option<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Discrepancy {
+    pub key: String,
+    pub claimed: String,
+    pub reported: String,
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is {} in the inventory but {} on the hardware",
+            self.key, self.claimed, self.reported
+        )
+    }
+}
+
+impl HardwareProfile {
+    /// Parses `/proc/cpuinfo`. On a Raspberry Pi the revision code also provides the board
+    /// model and memory size.
+    #[must_use]
+    pub fn from_cpuinfo(content: &str) -> Self {
+        let mut profile = HardwareProfile::default();
+        let mut processors = 0;
+        for (key, value) in key_values(content, ':') {
+            match key.as_str() {
+                "processor" => processors += 1,
+                "model name" | "Hardware" if profile.cpu_model.is_none() => {
+                    profile.cpu_model = Some(value);
+                }
+                "Model" => profile.board_model = Some(value),
+                "Revision" => profile.revision = Some(value.to_lowercase()),
+                "Serial" => profile.serial_number = SerialNumber::parse(&value).ok(),
+                _ => {}
+            }
+        }
+        if processors > 0 {
+            profile.cpu_cores = Some(processors);
+        }
+        if let Some(revision) = profile.revision.clone() {
+            if let Ok(revision) = PiRevision::parse(&revision) {
+                // the kernel reports BCM2835 as hardware on every model
+                profile.cpu_model = None;
+                profile.merge(&revision.get_profile());
+            }
+        }
+        profile
+    }
+
+    /// Parses `/proc/meminfo`.
+    #[must_use]
+    pub fn from_meminfo(content: &str) -> Self {
+        let mut profile = HardwareProfile::default();
+        for (key, value) in key_values(content, ':') {
+            if key == "MemTotal" {
+                profile.memory_mb = value
+                    .trim_end_matches("kB")
+                    .trim()
+                    .parse::<u64>()
+                    .ok()
+                    .map(|kb| kb / 1024);
+            }
+        }
+        profile
+    }
+
+    /// Parses the text output of `dmidecode`, using the system, processor and memory device
+    /// records.
+    #[must_use]
+    pub fn from_dmidecode(content: &str) -> Self {
+        let mut profile = HardwareProfile::default();
+        let mut memory_mb = 0;
+        for record in content.split("\n\n") {
+            let mut lines = record.lines().skip_while(|l| l.starts_with("Handle "));
+            let title = lines.next().unwrap_or_default().trim().to_string();
+            let values = key_values(&lines.collect::<Vec<&str>>().join("\n"), ':');
+            let get = |key: &str| {
+                values
+                    .iter()
+                    .find(|(k, _)| k == key)
+                    .map(|(_, v)| v.clone())
+                    .filter(|v| !is_placeholder(v))
+            };
+            match title.as_str() {
+                "System Information" => {
+                    profile.board_model = match (get("Manufacturer"), get("Product Name")) {
+                        (Some(manufacturer), Some(product)) => {
+                            Some(format!("{} {}", manufacturer, product))
+                        }
+                        (manufacturer, product) => product.or(manufacturer),
+                    };
+                    profile.serial_number =
+                        get("Serial Number").and_then(|s| SerialNumber::parse(&s).ok());
+                }
+                "Processor Information" => {
+                    if let Some(threads) = get("Thread Count").or_else(|| get("Core Count")) {
+                        if let Ok(threads) = threads.parse::<u32>() {
+                            profile.cpu_cores = Some(profile.cpu_cores.unwrap_or(0) + threads);
+                        }
+                    }
+                    if profile.cpu_model.is_none() {
+                        profile.cpu_model = get("Version");
+                    }
+                }
+                "Memory Device" => {
+                    if let Some(size) = get("Size") {
+                        if let Ok(size) = capacity::parse_size(&size.replace(' ', "")) {
+                            memory_mb += size;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        if memory_mb > 0 {
+            profile.memory_mb = Some(memory_mb);
+        }
+        profile
+    }
+
+    /// Parses the output of `lshw -json`, which is either a single object or, in newer
+    /// versions, a list of objects.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `content` is not valid JSON
+    pub fn from_lshw(content: &str) -> Result<Self, String> {
+        let json: serde_json::Value = serde_json::from_str(content).map_err(|e| e.to_string())?;
+        let mut profile = HardwareProfile::default();
+        match &json {
+            serde_json::Value::Array(entries) => {
+                for entry in entries {
+                    profile.add_lshw_entry(entry);
+                }
+            }
+            entry => profile.add_lshw_entry(entry),
+        }
+        Ok(profile)
+    }
+
+    fn add_lshw_entry(&mut self, entry: &serde_json::Value) {
+        let get = |key: &str| {
+            entry
+                .get(key)
+                .and_then(serde_json::Value::as_str)
+                .map(str::to_string)
+                .filter(|v| !is_placeholder(v))
+        };
+        let id = get("id").unwrap_or_default();
+        match get("class").unwrap_or_default().as_str() {
+            "system" if self.board_model.is_none() => {
+                self.board_model = match (get("vendor"), get("product")) {
+                    (Some(vendor), Some(product)) if !product.starts_with(&vendor) => {
+                        Some(format!("{} {}", vendor, product))
+                    }
+                    (vendor, product) => product.or(vendor),
+                };
+                self.serial_number = get("serial").and_then(|s| SerialNumber::parse(&s).ok());
+            }
+            "memory" if id == "memory" => {
+                if let Some(size) = entry.get("size").and_then(serde_json::Value::as_u64) {
+                    self.memory_mb = Some(self.memory_mb.unwrap_or(0) + size / 1024 / 1024);
+                }
+            }
+            "processor" => {
+                let configuration = entry.get("configuration");
+                let threads = ["threads", "cores"].iter().find_map(|key| {
+                    configuration
+                        .and_then(|c| c.get(*key))
+                        .and_then(serde_json::Value::as_str)
+                        .and_then(|v| v.parse::<u32>().ok())
+                });
+                if let Some(threads) = threads {
+                    self.cpu_cores = Some(self.cpu_cores.unwrap_or(0) + threads);
+                }
+                if self.cpu_model.is_none() {
+                    self.cpu_model = get("product");
+                }
+            }
+            "disk" if id.starts_with("disk") || id.starts_with("namespace") => {
+                if let Some(size) = entry.get("size").and_then(serde_json::Value::as_u64) {
+                    let name = get("logicalname").unwrap_or(id);
+                    let disk_type = get_disk_type(&name, &get("businfo").unwrap_or_default());
+                    self.disks.push(Disk {
+                        name,
+                        size_mb: size / 1024 / 1024,
+                        disk_type,
+                    });
+                }
+            }
+            _ => {}
+        }
+        if let Some(serde_json::Value::Array(children)) = entry.get("children") {
+            for child in children {
+                self.add_lshw_entry(child);
+            }
+        }
+    }
+
+    /// Fills every value that is unknown in this profile from `other`.
+    pub fn merge(&mut self, other: &HardwareProfile) {
+        if self.cpu_cores.is_none() {
+            self.cpu_cores = other.cpu_cores;
+        }
+        if self.cpu_model.is_none() {
+            self.cpu_model.clone_from(&other.cpu_model);
+        }
+        if self.memory_mb.is_none() {
+            self.memory_mb = other.memory_mb;
+        }
+        if self.disks.is_empty() {
+            self.disks.clone_from(&other.disks);
+        }
+        if self.board_model.is_none() {
+            self.board_model.clone_from(&other.board_model);
+        }
+        if self.serial_number.is_none() {
+            self.serial_number.clone_from(&other.serial_number);
+        }
+        if self.revision.is_none() {
+            self.revision.clone_from(&other.revision);
+        }
+    }
+
+    /// Returns the largest disk, which is taken as the one the inventory describes.
+    #[must_use]
+    pub fn get_primary_disk(&self) -> Option<&Disk> {
+        self.disks.iter().max_by_key(|d| d.size_mb)
+    }
+
+    #[must_use]
+    pub fn to_capacity(&self) -> Capacity {
+        let disk = self.get_primary_disk();
+        Capacity {
+            cpu_cores: self.cpu_cores,
+            memory_mb: self.memory_mb,
+            disk_mb: disk.map(|d| d.size_mb),
+            disk_type: disk.and_then(|d| d.disk_type.clone()),
+            board_model: self.board_model.clone(),
+        }
+    }
+
+    /// Compares the values claimed by `capacity` with this profile. Values missing on either
+    /// side are not compared.
+    #[must_use]
+    pub fn compare(&self, capacity: &Capacity) -> Vec<Discrepancy> {
+        let reported = self.to_capacity();
+        let mut discrepancies = Vec::new();
+        if let (Some(claimed), Some(actual)) = (capacity.cpu_cores, reported.cpu_cores) {
+            if claimed != actual {
+                discrepancies.push(discrepancy("cpu-cores", &claimed, &actual));
+            }
+        }
+        for (key, claimed, actual) in &[
+            ("memory", capacity.memory_mb, reported.memory_mb),
+            ("disk", capacity.disk_mb, reported.disk_mb),
+        ] {
+            if let (Some(claimed), Some(actual)) = (claimed, actual) {
+                if claimed.max(actual) - claimed.min(actual)
+                    > claimed * SIZE_TOLERANCE_PERCENT / 100
+                {
+                    discrepancies.push(discrepancy(
+                        key,
+                        &capacity::format_size(*claimed),
+                        &capacity::format_size(*actual),
+                    ));
+                }
+            }
+        }
+        if let (Some(claimed), Some(actual)) = (&capacity.disk_type, &reported.disk_type) {
+            if !claimed.eq_ignore_ascii_case(actual) {
+                discrepancies.push(discrepancy("disk-type", claimed, actual));
+            }
+        }
+        if let (Some(claimed), Some(actual)) = (&capacity.board_model, &reported.board_model) {
+            if !actual.to_lowercase().starts_with(&claimed.to_lowercase()) {
+                discrepancies.push(discrepancy("board-model", claimed, actual));
+            }
+        }
+        discrepancies
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PiRevision {
+    pub code: u32,
+    pub model: &'static str,
+    pub processor: &'static str,
+    pub memory_mb: u64,
+}
+
+impl PiRevision {
+    /// Decodes the revision code reported in `/proc/cpuinfo` of a Raspberry Pi, for example
+    /// `c03111` for a Raspberry Pi 4 Model B with 4 GB.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `code` is not a known revision code
+    pub fn parse(code: &str) -> Result<PiRevision, String> {
+        let trimmed = code.trim().trim_start_matches("0x");
+        let value = u32::from_str_radix(trimmed, 16)
+            .map_err(|_| format!("{} is not a revision code", code))?;
+        if value & (1 << 23) == 0 {
+            let (model, memory_mb) = match value & 0xff_ffff {
+                0x02..=0x06 => ("Raspberry Pi Model B", 256),
+                0x07..=0x09 => ("Raspberry Pi Model A", 256),
+                0x0d..=0x0f => ("Raspberry Pi Model B", 512),
+                0x10 | 0x13 => ("Raspberry Pi Model B+", 512),
+                0x11 | 0x14 => ("Raspberry Pi Compute Module 1", 512),
+                0x12 | 0x15 => ("Raspberry Pi Model A+", 256),
+                _ => return Err(format!("{} is not a known revision code", code)),
+            };
+            return Ok(PiRevision {
+                code: value,
+                model,
+                processor: "BCM2835",
+                memory_mb,
+            });
+        }
+        let memory_mb = match (value >> 20) & 0x7 {
+            0 => 256,
+            1 => 512,
+            2 => 1024,
+            3 => 2048,
+            4 => 4096,
+            5 => 8192,
+            6 => 16384,
+            _ => return Err(format!("{} has an unknown memory size", code)),
+        };
+        let processor = match (value >> 12) & 0xf {
+            0 => "BCM2835",
+            1 => "BCM2836",
+            2 => "BCM2837",
+            3 => "BCM2711",
+            4 => "BCM2712",
+            _ => return Err(format!("{} has an unknown processor", code)),
+        };
+        let model = match (value >> 4) & 0xff {
+            0x00 => "Raspberry Pi Model A",
+            0x01 => "Raspberry Pi Model B",
+            0x02 => "Raspberry Pi Model A+",
+            0x03 => "Raspberry Pi Model B+",
+            0x04 => "Raspberry Pi 2 Model B",
+            0x06 => "Raspberry Pi Compute Module 1",
+            0x08 => "Raspberry Pi 3 Model B",
+            0x09 => "Raspberry Pi Zero",
+            0x0a => "Raspberry Pi Compute Module 3",
+            0x0c => "Raspberry Pi Zero W",
+            0x0d => "Raspberry Pi 3 Model B+",
+            0x0e => "Raspberry Pi 3 Model A+",
+            0x10 => "Raspberry Pi Compute Module 3+",
+            0x11 => "Raspberry Pi 4 Model B",
+            0x12 => "Raspberry Pi Zero 2 W",
+            0x13 => "Raspberry Pi 400",
+            0x14 => "Raspberry Pi Compute Module 4",
+            0x15 => "Raspberry Pi Compute Module 4S",
+            0x17 => "Raspberry Pi 5",
+            0x18 => "Raspberry Pi Compute Module 5",
+            0x19 => "Raspberry Pi 500",
+            0x1a => "Raspberry Pi Compute Module 5 Lite",
+            _ => return Err(format!("{} has an unknown board type", code)),
+        };
+        Ok(PiRevision {
+            code: value,
+            model,
+            processor,
+            memory_mb,
+        })
+    }
+
+    #[must_use]
+    pub fn get_cpu_cores(&self) -> u32 {
+        match self.processor {
+            "BCM2835" => 1,
+            _ => 4,
+        }
+    }
+
+    #[must_use]
+    pub fn get_profile(&self) -> HardwareProfile {
+        HardwareProfile {
+            cpu_cores: Some(self.get_cpu_cores()),
+            cpu_model: Some(self.processor.to_string()),
+            memory_mb: Some(self.memory_mb),
+            board_model: Some(self.model.to_string()),
+            revision: Some(format!("{:x}", self.code)),
+            ..HardwareProfile::default()
+        }
+    }
+}
+
+fn key_values(content: &str, separator: char) -> Vec<(String, String)> {
+    content
+        .lines()
+        .filter_map(|l| l.split_once(separator))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect()
+}
+
+fn is_placeholder(value: &str) -> bool {
+    let lower = value.trim().to_lowercase();
+    lower.is_empty()
+        || lower == "not specified"
+        || lower == "not present"
+        || lower == "no module installed"
+        || lower == "unknown"
+        || lower.contains("to be filled")
+        || lower.contains("default string")
+}
+
+fn get_disk_type(name: &str, businfo: &str) -> Option<String> {
+    let disk_type = if name.starts_with("/dev/nvme") || businfo.starts_with("nvme") {
+        "nvme"
+    } else if name.starts_with("/dev/mmcblk") || businfo.starts_with("mmc") {
+        "mmc"
+    } else if businfo.starts_with("usb") {
+        "usb"
+    } else {
+        return None;
+    };
+    Some(disk_type.to_string())
+}
+
+fn discrepancy<C: fmt::Display, R: fmt::Display>(
+    key: &str,
+    claimed: &C,
+    reported: &R,
+) -> Discrepancy {
+    Discrepancy {
+        key: key.to_string(),
+        claimed: claimed.to_string(),
+        reported: reported.to_string(),
+    }
+}
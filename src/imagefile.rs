@@ -1,5 +1,5 @@
 use crate::architecture::Architecture;
-use crate::bootconfig::BootConfig;
+use crate::bootconfig::{BootConfig, BootConflict};
 use crate::configuration::Configuration;
 use crate::partition::Partition;
 use crate::post_provisioner::PostProvisioner;
@@ -97,8 +97,10 @@ impl Imagefile {
         builder.string().unwrap_or_default()
     }
 
-    #[must_use]
-    pub fn get_boot_files(&self) -> BootConfig {
+    /// # Errors
+    ///
+    /// Will return `Err` if the boot configurations map a destination to different files
+    pub fn get_boot_files(&self) -> Result<BootConfig, Vec<BootConflict>> {
         crate::bootconfig::group(&self.configuration.bootconfigs)
     }
 }
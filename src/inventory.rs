@@ -0,0 +1,622 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::mem;
+
+use config::{Config, File, FileFormat, Value};
+use string_builder::Builder;
+
+use crate::logsource::{LogSource, LogSourceTypes};
+use crate::node::Node;
+use crate::node_group;
+use crate::node_group::NodeGroups;
+use crate::power_config::PowerConfig;
+use crate::utils;
+
+const HOST_LOGS: &str = "host-logs";
+const SERIAL_LOGS: &str = "serial-logs";
+const GROUPS: &str = "groups";
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct InventoryError {
+    pub node: String,
+    pub key: String,
+    pub message: String,
+}
+
+impl InventoryError {
+    #[must_use]
+    pub fn new(node: &str, key: &str, message: &str) -> Self {
+        InventoryError {
+            node: String::from(node),
+            key: String::from(key),
+            message: String::from(message),
+        }
+    }
+}
+
+impl fmt::Display for InventoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.node.is_empty(), self.key.is_empty()) {
+            (true, true) => write!(f, "{}", self.message),
+            (true, false) => write!(f, "{}: {}", self.key, self.message),
+            (false, true) => write!(f, "node {}: {}", self.node, self.message),
+            (false, false) => write!(f, "node {}: {} {}", self.node, self.key, self.message),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Inventory {
+    nodes: Vec<Node>,
+    groups: NodeGroups,
+}
+
+impl Inventory {
+    #[must_use]
+    pub fn new() -> Self {
+        Inventory {
+            nodes: Vec::new(),
+            groups: NodeGroups::default(),
+        }
+    }
+
+    /// Loads every node of a table keyed by node id. Log sources are read from the optional
+    /// `host-logs` and `serial-logs` lists of each node.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` with every problem of every node
+    pub fn from_config(nodes: &HashMap<String, Value>) -> Result<Self, Vec<InventoryError>> {
+        Inventory::from_config_with_groups(nodes, NodeGroups::default())
+    }
+
+    /// Loads the nodes like `from_config` and lets them inherit the settings of their group.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` with every problem of every node
+    pub fn from_config_with_groups(
+        nodes: &HashMap<String, Value>,
+        groups: NodeGroups,
+    ) -> Result<Self, Vec<InventoryError>> {
+        let mut errors = Vec::new();
+        let mut inventory = Inventory::new();
+        inventory.groups = groups;
+        let mut ids = nodes.keys().collect::<Vec<&String>>();
+        ids.sort();
+        for id in ids {
+            let mut hash = match nodes[id].clone().into_table() {
+                Ok(hash) => hash,
+                Err(e) => {
+                    errors.push(InventoryError::new(id, "", &e.to_string()));
+                    continue;
+                }
+            };
+            let mut log_inputs = Vec::new();
+            for (key, constructor) in &[
+                (HOST_LOGS, LogSource::host as fn(String) -> LogSource),
+                (SERIAL_LOGS, LogSource::serial),
+            ] {
+                match get_list(hash.remove(*key)) {
+                    Ok(paths) => log_inputs.extend(paths.into_iter().map(constructor)),
+                    Err(e) => errors.push(InventoryError::new(id, key, &e)),
+                }
+            }
+            let inherit_architecture = !hash.contains_key("architecture");
+            match Node::from_config(id.clone(), &hash, log_inputs) {
+                Ok(mut node) => match inventory
+                    .resolve_groups(&mut node, inherit_architecture)
+                    .and_then(|()| inventory.check(&node))
+                {
+                    Ok(()) => inventory.nodes.push(node),
+                    Err(mut e) => errors.append(&mut e),
+                },
+                Err(mut e) => errors.append(&mut e),
+            }
+        }
+        let labels = inventory
+            .nodes
+            .iter()
+            .flat_map(|n| n.labels.keys())
+            .map(String::as_str)
+            .collect();
+        if let Err(mut e) = inventory.groups.check_placeholders(&labels) {
+            errors.append(&mut e);
+        }
+        if errors.is_empty() {
+            Ok(inventory)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Parses the TOML document `content` and loads the nodes of its `section` table and the
+    /// groups of its optional `groups` table. Configuration crates that lowercase keys while
+    /// reading lowercase node ids as well, so [`Inventory::write`] refuses to rename them and
+    /// new nodes must have lowercase ids.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `content` could not be parsed or contains invalid nodes
+    pub fn from_toml(content: &str, section: &str) -> Result<Self, Vec<InventoryError>> {
+        let mut config = Config::default();
+        let nodes = config
+            .merge(File::from_str(content, FileFormat::Toml))
+            .and_then(|c| c.get_table(section))
+            .map_err(|e| vec![InventoryError::new("", section, &e.to_string())])?;
+        let groups = match config.get_table(GROUPS) {
+            Ok(groups) => NodeGroups::from_config(&groups)?,
+            Err(_) => NodeGroups::default(),
+        };
+        Inventory::from_config_with_groups(&nodes, groups)
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if `filename` could not be read or contains invalid nodes
+    pub fn read(filename: &str, section: &str) -> Result<Self, Vec<InventoryError>> {
+        let content = fs::read_to_string(filename)
+            .map_err(|e| vec![InventoryError::new("", "", &e.to_string())])?;
+        Inventory::from_toml(&content, section)
+    }
+
+    /// Replaces the `section` and `groups` tables of `filename` with the current nodes and
+    /// groups, keeping every other table, key and comment as it is.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `filename` could not be read or written to, or if it has node ids
+    /// with uppercase letters that were lowercased while reading, which writing would rename
+    pub fn write(&self, filename: &str, section: &str) -> io::Result<()> {
+        let current = match fs::read_to_string(filename) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        let renamed = get_uppercase_ids(&current, section)
+            .into_iter()
+            .filter(|id| self.get(id).is_none() && self.get(&id.to_lowercase()).is_some())
+            .collect::<Vec<String>>();
+        if !renamed.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{} has node ids with uppercase letters, which would be renamed: {}",
+                    filename,
+                    renamed.join(", ")
+                ),
+            ));
+        }
+        let updated = replace_tables(&current, &[section, GROUPS], &self.to_toml(section));
+        if updated.eq(&current) {
+            return Ok(());
+        }
+        utils::write_file(filename, &updated)
+    }
+
+    #[must_use]
+    pub fn get_nodes(&self) -> Vec<Node> {
+        self.nodes.clone()
+    }
+
+    /// Returns the nodes new services may be placed on.
+    #[must_use]
+    pub fn get_schedulable_nodes(&self) -> Vec<Node> {
+        self.nodes
+            .iter()
+            .filter(|n| n.is_schedulable())
+            .cloned()
+            .collect()
+    }
+
+    #[must_use]
+    pub fn get_groups(&self) -> NodeGroups {
+        self.groups.clone()
+    }
+
+    /// Returns the nodes of `group`, including those of the groups nested in it.
+    #[must_use]
+    pub fn get_nodes_in_group(&self, group: &str) -> Vec<Node> {
+        node_group::get_members(&self.nodes, group)
+    }
+
+    #[must_use]
+    pub fn get(&self, id: &str) -> Option<&Node> {
+        self.nodes.iter().find(|n| n.id.eq(id))
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if the id, MAC address or IPv4 address of `node` is already in use,
+    /// its id has uppercase letters or its group does not exist
+    pub fn add(&mut self, node: Node) -> Result<(), Vec<InventoryError>> {
+        check_id(&node.id)?;
+        let mut node = node;
+        self.resolve_groups(&mut node, false)?;
+        self.check(&node)?;
+        self.nodes.push(node);
+        Ok(())
+    }
+
+    /// Replaces the node with the same id.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if there is no such node, its MAC or IPv4 address is used elsewhere or
+    /// its group does not exist
+    pub fn update(&mut self, node: Node) -> Result<(), Vec<InventoryError>> {
+        let mut node = node;
+        self.resolve_groups(&mut node, false)?;
+        let index = match self.nodes.iter().position(|n| n.id.eq(&node.id)) {
+            Some(index) => index,
+            None => return Err(vec![InventoryError::new(&node.id, "", "does not exist")]),
+        };
+        let previous = self.nodes.remove(index);
+        if let Err(e) = self.check(&node) {
+            self.nodes.insert(index, previous);
+            return Err(e);
+        }
+        self.nodes.insert(index, node);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, id: &str) -> Option<Node> {
+        let index = self.nodes.iter().position(|n| n.id.eq(id))?;
+        Some(self.nodes.remove(index))
+    }
+
+    #[must_use]
+    pub fn to_toml(&self, section: &str) -> String {
+        let mut builder = Builder::default();
+        for (i, node) in self.nodes.iter().enumerate() {
+            if i > 0 {
+                builder.append("\n");
+            }
+            builder.append(format!("[{}.{}]\n", section, toml_key(&node.id)));
+            let mut values = vec![
+                ("name", toml_string(&node.name)),
+                ("tftp-prefix", toml_string(&node.tftp_prefix)),
+                ("mac-address", toml_string(&node.mac_address.to_string())),
+                ("ipv4-address", toml_string(&node.ipv4_address.to_string())),
+                (
+                    "serial-number",
+                    toml_string(&node.serial_number.to_string()),
+                ),
+                ("architecture", toml_string(node.architecture.get_name())),
+                ("pxe", node.pxe.to_string()),
+            ];
+            if let Some(group) = node.get_group() {
+                values.push(("group", toml_string(group)));
+            }
+            if let Some(tftp_server) = &node.tftp_server {
+                values.push(("tftp-server", toml_string(tftp_server)));
+            }
+            if let Some(power) = node.get_own_power() {
+                values.push(("power", toml_power(&power)));
+            }
+            values.retain(|(key, _)| !node.is_inherited(key));
+            for (key, value) in node.capacity.get_values() {
+                values.push((
+                    key,
+                    match key {
+                        "cpu-cores" => value,
+                        _ => toml_string(&value),
+                    },
+                ));
+            }
+            if let Some(maintenance) = &node.maintenance {
+                let mut entries = vec![format!("reason = {}", toml_string(&maintenance.reason))];
+                if let Some(until) = maintenance.until {
+                    entries.push(format!(
+                        "until = {}",
+                        toml_string(&until.format("%Y-%m-%d %H:%M:%S").to_string())
+                    ));
+                }
+                values.push(("maintenance", format!("{{ {} }}", entries.join(", "))));
+            }
+            if !node.labels.is_empty() {
+                values.push(("labels", toml_labels(node)));
+            }
+            for (key, source) in &[
+                (HOST_LOGS, LogSourceTypes::HOST),
+                (SERIAL_LOGS, LogSourceTypes::SERIAL),
+            ] {
+                let paths = node
+                    .log_inputs
+                    .iter()
+                    .filter(|l| l.source.eq(source))
+                    .map(|l| toml_string(&l.path))
+                    .collect::<Vec<String>>();
+                if !paths.is_empty() {
+                    values.push((key, format!("[{}]", paths.join(", "))));
+                }
+            }
+            for (key, value) in &values {
+                builder.append(format!("{} = {}\n", key, value));
+            }
+            for (key, value) in &node.extra {
+                if let Some(value) = toml_value(value) {
+                    builder.append(format!("{} = {}\n", toml_key(key), value));
+                }
+            }
+        }
+        for group in self.groups.get_groups() {
+            builder.append(format!("\n[{}.{}]\n", GROUPS, toml_key(&group.id)));
+            let settings = &group.settings;
+            for (key, value) in &[
+                ("kind", group.kind.as_ref().map(|k| toml_string(k))),
+                ("parent", group.parent.as_ref().map(|p| toml_string(p))),
+                (
+                    "architecture",
+                    settings
+                        .architecture
+                        .as_ref()
+                        .map(|a| toml_string(a.get_name())),
+                ),
+                (
+                    "tftp-server",
+                    settings.tftp_server.as_ref().map(|t| toml_string(t)),
+                ),
+                ("power", settings.power.as_ref().map(toml_power)),
+            ] {
+                if let Some(value) = value {
+                    builder.append(format!("{} = {}\n", key, value));
+                }
+            }
+        }
+        builder.string().unwrap_or_default()
+    }
+
+    fn resolve_groups(
+        &self,
+        node: &mut Node,
+        inherit_architecture: bool,
+    ) -> Result<(), Vec<InventoryError>> {
+        self.groups
+            .apply(node, inherit_architecture)
+            .map_err(|e| vec![InventoryError::new(&node.id, "group", &e)])
+    }
+
+    fn check(&self, node: &Node) -> Result<(), Vec<InventoryError>> {
+        let mut errors = Vec::new();
+        for other in &self.nodes {
+            if other.id.eq(&node.id) {
+                errors.push(InventoryError::new(&node.id, "", "already exists"));
+            }
+            if other.mac_address.eq(&node.mac_address) {
+                errors.push(InventoryError::new(
+                    &node.id,
+                    "mac-address",
+                    &format!("{} is already used by node {}", node.mac_address, other.id),
+                ));
+            }
+            if other.ipv4_address.eq(&node.ipv4_address) {
+                errors.push(InventoryError::new(
+                    &node.id,
+                    "ipv4-address",
+                    &format!("{} is already used by node {}", node.ipv4_address, other.id),
+                ));
+            }
+        }
+        if let Some(Err(e)) = node
+            .power
+            .as_ref()
+            .map(|p| p.check().and_then(|()| p.render(node)))
+        {
+            errors.push(InventoryError::new(&node.id, "power", &e));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Removes the tables below any of `names` from the TOML document `content` and puts `tables`
+/// where the first of them was, or at the end. Comments right above a table that is kept stay
+/// with it.
+fn replace_tables(content: &str, names: &[&str], tables: &str) -> String {
+    let mut kept: Vec<&str> = Vec::new();
+    let mut comments: Vec<&str> = Vec::new();
+    let mut position = None;
+    let mut replacing = false;
+    for line in content.lines() {
+        if let Some(name) = get_table_name(line) {
+            let replace = names.contains(&name.as_str());
+            if replace {
+                position.get_or_insert(kept.len());
+            } else if replacing {
+                kept.append(&mut comments);
+            }
+            comments.clear();
+            replacing = replace;
+            if replacing {
+                continue;
+            }
+        }
+        if !replacing {
+            kept.push(line);
+        } else if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            comments.push(line);
+        } else {
+            comments.clear();
+        }
+    }
+    let mut lines = kept
+        .iter()
+        .map(|l| (*l).to_string())
+        .collect::<Vec<String>>();
+    let tables = tables.trim_end().lines().map(str::to_string);
+    if let Some(position) = position {
+        let mut replacement = tables.collect::<Vec<String>>();
+        if lines.get(position).is_some_and(|l| !l.trim().is_empty()) {
+            replacement.push(String::new());
+        }
+        lines.splice(position..position, replacement);
+    } else {
+        if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+            lines.push(String::new());
+        }
+        lines.extend(tables);
+    }
+    let mut updated = lines.join("\n");
+    updated.push('\n');
+    updated
+}
+
+/// Rejects ids with uppercase letters, which reading the inventory may lowercase.
+fn check_id(id: &str) -> Result<(), Vec<InventoryError>> {
+    if id.chars().any(char::is_uppercase) {
+        return Err(vec![InventoryError::new(
+            id,
+            "",
+            "must not contain uppercase letters, as the inventory may be read lowercased",
+        )]);
+    }
+    Ok(())
+}
+
+/// Returns the first key of a table header such as `[nodes.pi-1]` or `[["nodes"]]`.
+fn get_table_name(line: &str) -> Option<String> {
+    get_table_path(line)?.into_iter().next()
+}
+
+/// Returns the keys of a table header, such as `nodes` and `pi-1` for `[nodes."pi-1"]`.
+fn get_table_path(line: &str) -> Option<Vec<String>> {
+    let line = line.split('#').next().unwrap_or_default().trim();
+    let header = line.strip_prefix('[')?.strip_suffix(']')?;
+    let header = header.strip_prefix('[').unwrap_or(header);
+    let header = header.strip_suffix(']').unwrap_or(header);
+    let mut keys = Vec::new();
+    let mut key = String::new();
+    let mut quoted = false;
+    for c in header.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            '.' if !quoted => keys.push(mem::take(&mut key).trim().to_string()),
+            c => key.push(c),
+        }
+    }
+    keys.push(key.trim().to_string());
+    Some(keys)
+}
+
+/// Returns the node ids of the `section` table of `content` that have uppercase letters.
+fn get_uppercase_ids(content: &str, section: &str) -> Vec<String> {
+    let mut ids: Vec<String> = Vec::new();
+    let mut in_section = false;
+    for line in content.lines() {
+        let id = match get_table_path(line) {
+            Some(path) => {
+                in_section = path.len() == 1 && path[0].eq(section);
+                match path.get(1) {
+                    Some(id) if path[0].eq(section) => id.clone(),
+                    _ => continue,
+                }
+            }
+            None if in_section && line.contains('=') => line
+                .split('=')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .trim_matches('"')
+                .to_string(),
+            None => continue,
+        };
+        if id.chars().any(char::is_uppercase) && !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+    ids
+}
+
+fn get_list(value: Option<Value>) -> Result<Vec<String>, String> {
+    match value {
+        Some(value) => value
+            .into_array()
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|v| v.into_str().map_err(|e| e.to_string()))
+            .collect(),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn toml_labels(node: &Node) -> String {
+    let labels = node
+        .get_labels()
+        .iter()
+        .map(|l| toml_string(l))
+        .collect::<Vec<String>>();
+    utils::vec_to_string(&labels, false)
+}
+
+fn toml_power(power: &PowerConfig) -> String {
+    let entries = power
+        .get_values()
+        .into_iter()
+        .map(|(key, value)| format!("{} = {}", key, toml_string(&value)))
+        .collect::<Vec<String>>();
+    format!("{{ {} }}", entries.join(", "))
+}
+
+fn toml_key(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return key.to_string();
+    }
+    toml_string(key)
+}
+
+fn toml_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            c if c.is_control() => {
+                // Writing to a String cannot fail.
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    format!("\"{}\"", escaped)
+}
+
+fn toml_value(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::Null => None,
+        serde_json::Value::Bool(b) => Some(b.to_string()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        serde_json::Value::String(s) => Some(toml_string(s)),
+        serde_json::Value::Array(values) => Some(utils::vec_to_string(
+            &values
+                .iter()
+                .filter_map(toml_value)
+                .collect::<Vec<String>>(),
+            false,
+        )),
+        serde_json::Value::Object(map) => {
+            let mut entries = map
+                .iter()
+                .filter_map(|(k, v)| toml_value(v).map(|v| format!("{} = {}", toml_key(k), v)))
+                .collect::<Vec<String>>();
+            entries.sort();
+            if entries.is_empty() {
+                return Some(String::from("{}"));
+            }
+            Some(format!("{{ {} }}", entries.join(", ")))
+        }
+    }
+}
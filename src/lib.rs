@@ -1,24 +1,50 @@
 pub mod architecture;
 pub mod arm_preamble;
 pub mod bootconfig;
+pub mod capacity;
+pub mod cmdline_txt;
+pub mod command_runner;
+pub mod config_txt;
 pub mod configuration;
 pub mod deployment;
 pub mod deployment_row;
+pub mod dhcp;
+pub mod discovery;
+pub mod diskless;
+pub mod dry_run;
+pub mod hardware_profile;
 pub mod image;
 pub mod image_row;
 pub mod imagefile;
+pub mod inventory;
 pub mod logsource;
+pub mod mac_address;
+pub mod maintenance;
 pub mod mountpoint;
 pub mod node;
+pub mod node_group;
 pub mod node_row;
+pub mod node_selector;
+pub mod node_state;
 pub mod partition;
 pub mod post_provisioner;
 pub mod power_action;
 pub mod power_action_set;
+pub mod power_audit;
+pub mod power_backend;
+pub mod power_batch;
+pub mod power_config;
 pub mod preamble;
+pub mod probe;
 pub mod provisioner;
+pub mod reservation;
+pub mod retry_policy;
+pub mod serial_number;
 pub mod service;
 pub mod service_row;
 pub mod task;
+pub mod template;
+pub mod tftp;
 pub mod utils;
 pub mod x86_preamble;
+pub mod yaml_schema;
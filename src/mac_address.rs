@@ -0,0 +1,85 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[serde(try_from = "String", into = "String")]
+pub struct MacAddress([u8; 6]);
+
+impl MacAddress {
+    #[must_use]
+    pub fn new(octets: [u8; 6]) -> Self {
+        MacAddress(octets)
+    }
+
+    /// Accepts `aa:bb:cc:dd:ee:ff`, `aa-bb-cc-dd-ee-ff`, `aabb.ccdd.eeff` and `aabbccddeeff`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `value` is not a MAC address or consists of zeros only
+    pub fn parse(value: &str) -> Result<MacAddress, String> {
+        let digits = value
+            .trim()
+            .chars()
+            .filter(|c| !matches!(c, ':' | '-' | '.'))
+            .collect::<String>();
+        if digits.len() != 12 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("{} is not a valid MAC address", value));
+        }
+        let mut octets = [0; 6];
+        for (i, octet) in octets.iter_mut().enumerate() {
+            *octet = u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16)
+                .map_err(|_| format!("{} is not a valid MAC address", value))?;
+        }
+        if octets.iter().all(|o| *o == 0) {
+            return Err(format!("{} is not a usable MAC address", value));
+        }
+        Ok(MacAddress(octets))
+    }
+
+    #[must_use]
+    pub fn get_octets(&self) -> [u8; 6] {
+        self.0
+    }
+
+    /// Returns the address as `aa-bb-cc-dd-ee-ff`, as used in PXE file names.
+    #[must_use]
+    pub fn to_dashed(&self) -> String {
+        self.to_string().replace(':', "-")
+    }
+}
+
+impl fmt::Display for MacAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let o = self.0;
+        write!(
+            f,
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            o[0], o[1], o[2], o[3], o[4], o[5]
+        )
+    }
+}
+
+impl FromStr for MacAddress {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        MacAddress::parse(s)
+    }
+}
+
+impl TryFrom<String> for MacAddress {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        MacAddress::parse(&value)
+    }
+}
+
+impl From<MacAddress> for String {
+    fn from(mac_address: MacAddress) -> Self {
+        mac_address.to_string()
+    }
+}
@@ -0,0 +1,176 @@
+use std::fmt;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use config::Value;
+use serde::{Deserialize, Serialize};
+
+use crate::deployment::Deployment;
+use crate::node::Node;
+use crate::node_state::NodeState;
+use crate::service::Service;
+
+const DATE_TIME_FORMATS: [&str; 3] = ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"];
+
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+pub struct Maintenance {
+    pub reason: String,
+    pub until: Option<NaiveDateTime>,
+}
+
+impl Maintenance {
+    #[must_use]
+    pub fn new(reason: &str, until: Option<NaiveDateTime>) -> Self {
+        Maintenance {
+            reason: String::from(reason),
+            until,
+        }
+    }
+
+    /// Accepts a reason or a table with `reason` and an optional `until`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `value` has no reason or `until` is not a date
+    pub fn from_config(value: &Value) -> Result<Self, String> {
+        if let Ok(reason) = value.clone().into_str() {
+            return Ok(Maintenance::new(&reason, None));
+        }
+        let mut table = value.clone().into_table().map_err(|e| e.to_string())?;
+        let reason = table
+            .remove("reason")
+            .ok_or_else(|| String::from("is missing a reason"))?
+            .into_str()
+            .map_err(|e| e.to_string())?;
+        let until = match table.remove("until") {
+            Some(until) => Some(parse_date_time(
+                &until.into_str().map_err(|e| e.to_string())?,
+            )?),
+            None => None,
+        };
+        Ok(Maintenance::new(&reason, until))
+    }
+
+    /// Returns whether the expected end has passed without the maintenance being ended.
+    #[must_use]
+    pub fn is_overdue(&self, now: NaiveDateTime) -> bool {
+        match self.until {
+            Some(until) => until < now,
+            None => false,
+        }
+    }
+}
+
+impl fmt::Display for Maintenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.reason)?;
+        if let Some(until) = self.until {
+            write!(f, " (until {})", until.format("%Y-%m-%d %H:%M"))?;
+        }
+        Ok(())
+    }
+}
+
+/// What has to happen before a node in maintenance is free of work.
+#[derive(Debug, Clone)]
+pub struct DrainReport {
+    pub node: String,
+    pub services: Vec<Service>,
+    pub deployments: Vec<Deployment>,
+}
+
+impl DrainReport {
+    /// Returns whether nothing is left running on the node.
+    #[must_use]
+    pub fn is_drained(&self) -> bool {
+        self.services.is_empty()
+    }
+
+    /// Returns the lifecycle state the node should be in: draining while services are left,
+    /// maintenance afterwards.
+    #[must_use]
+    pub fn get_state(&self) -> NodeState {
+        if self.is_drained() {
+            NodeState::Maintenance
+        } else {
+            NodeState::Draining
+        }
+    }
+}
+
+impl fmt::Display for DrainReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_drained() {
+            return write!(f, "node {} is drained", self.node);
+        }
+        writeln!(
+            f,
+            "node {} still runs {} service(s):",
+            self.node,
+            self.services.len()
+        )?;
+        for service in &self.services {
+            writeln!(f, "  service {} ({})", service.name, service.image)?;
+        }
+        for deployment in &self.deployments {
+            writeln!(
+                f,
+                "  deployment {} of {} must be moved or ended",
+                deployment.name, deployment.owner
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Puts `node` into maintenance so no new services are scheduled on it and reports which of
+/// the running `services` and their `deployments` must be moved or ended first.
+pub fn drain(
+    node: &mut Node,
+    maintenance: Maintenance,
+    services: &[Service],
+    deployments: &[Deployment],
+) -> DrainReport {
+    node.maintenance = Some(maintenance);
+    get_drain_report(node, services, deployments)
+}
+
+#[must_use]
+pub fn get_drain_report(
+    node: &Node,
+    services: &[Service],
+    deployments: &[Deployment],
+) -> DrainReport {
+    let services = services
+        .iter()
+        .filter(|s| s.end.is_none() && s.node.as_deref() == Some(node.id.as_str()))
+        .cloned()
+        .collect::<Vec<Service>>();
+    let deployments = deployments
+        .iter()
+        .filter(|d| {
+            d.end.is_none()
+                && services.iter().any(|s| {
+                    (s.deployment.is_some() && s.deployment == d.id)
+                        || d.services.iter().any(|ds| ds.id.is_some() && ds.id == s.id)
+                })
+        })
+        .cloned()
+        .collect();
+    DrainReport {
+        node: node.id.clone(),
+        services,
+        deployments,
+    }
+}
+
+fn parse_date_time(value: &str) -> Result<NaiveDateTime, String> {
+    for format in &DATE_TIME_FORMATS {
+        if let Ok(date_time) = NaiveDateTime::parse_from_str(value, format) {
+            return Ok(date_time);
+        }
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .ok_or_else(|| format!("{} is not a date", value))
+}
@@ -1,84 +1,371 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::net::Ipv4Addr;
 
 use config::Value;
 use serde::{Deserialize, Serialize};
 
 use crate::architecture::Architecture;
+use crate::capacity;
+use crate::capacity::Capacity;
+use crate::hardware_profile::{Discrepancy, HardwareProfile};
+use crate::inventory::InventoryError;
 use crate::logsource::LogSource;
+use crate::mac_address::MacAddress;
+use crate::maintenance::Maintenance;
+use crate::power_backend;
+use crate::power_backend::PowerBackend;
+use crate::power_config::PowerConfig;
+use crate::serial_number::SerialNumber;
+
+/// The fields power command templates can refer to, besides labels.
+pub const FIELDS: [&str; 8] = [
+    "id",
+    "name",
+    "tftp-prefix",
+    "mac-address",
+    "serial-number",
+    "ipv4-address",
+    "architecture",
+    "group",
+];
+
+pub const KEYS: [&str; 12] = [
+    "name",
+    "tftp-prefix",
+    "mac-address",
+    "ipv4-address",
+    "serial-number",
+    "architecture",
+    "pxe",
+    "labels",
+    "maintenance",
+    "group",
+    "tftp-server",
+    "power",
+];
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Node {
     pub id: String,
     pub name: String,
     pub tftp_prefix: String,
-    pub mac_address: String,
-    pub serial_number: String,
-    pub ipv4_address: String,
+    pub mac_address: MacAddress,
+    pub serial_number: SerialNumber,
+    pub ipv4_address: Ipv4Addr,
     pub log_inputs: Vec<LogSource>,
     pub architecture: Architecture,
     pub pxe: bool,
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    #[serde(default)]
+    pub capacity: Capacity,
+    #[serde(default)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub hardware: Option<HardwareProfile>,
+    #[serde(default)]
+    pub maintenance: Option<Maintenance>,
+    /// The group of the node followed by the groups it is nested in.
+    #[serde(default)]
+    pub groups: Vec<String>,
+    #[serde(default)]
+    pub tftp_server: Option<String>,
+    #[serde(default)]
+    pub power: Option<PowerConfig>,
+    /// Keys whose values come from a group rather than the node itself.
+    #[serde(default)]
+    pub inherited: Vec<String>,
 }
 
 impl Node {
-    #[must_use]
+    /// # Errors
+    ///
+    /// Will return `Err` with every key of `hash` that is missing or invalid
     pub fn from_config(
         id: String,
         hash: &HashMap<String, Value>,
         log_inputs: Vec<LogSource>,
-    ) -> Self {
-        Node {
-            id,
-            name: hash
-                .get("name")
-                .unwrap_or(&Value::from("node"))
-                .clone()
-                .into_str()
-                .unwrap_or_default(),
-            tftp_prefix: hash
-                .get("tftp-prefix")
-                .unwrap_or(&Value::from("00-00-00-00-00-00"))
-                .clone()
-                .into_str()
-                .unwrap_or_default(),
-            mac_address: hash
-                .get("mac-address")
-                .unwrap_or(&Value::from("00-00-00-00-00-00"))
-                .clone()
-                .into_str()
-                .unwrap_or_default(),
-            ipv4_address: hash
-                .get("ipv4-address")
-                .unwrap_or(&Value::from("0.0.0.0"))
-                .clone()
-                .into_str()
-                .unwrap_or_default(),
-            serial_number: hash
-                .get("serial-number")
-                .unwrap_or(&Value::from("00-00-00-00-00-00"))
-                .clone()
-                .into_str()
-                .unwrap_or_default(),
-            log_inputs,
-            architecture: Architecture::parse(
-                &hash
-                    .get("architecture")
-                    .unwrap_or(&Value::from("ARM64"))
-                    .clone()
-                    .into_str()
-                    .unwrap_or_default(),
-            )
-            .unwrap_or(Architecture::ARM64),
-            pxe: if hash.contains_key("pxe") {
-                hash.get("pxe")
-                    .unwrap_or(&Value::from("false"))
-                    .clone()
-                    .into_bool()
-                    .unwrap_or(false)
+    ) -> Result<Self, Vec<InventoryError>> {
+        let mut errors = Vec::new();
+        let name = get_value(&id, hash, "name", &mut errors, |v| Ok(v.to_string()));
+        let tftp_prefix = get_value(&id, hash, "tftp-prefix", &mut errors, parse_tftp_prefix);
+        let mac_address = get_value(&id, hash, "mac-address", &mut errors, MacAddress::parse);
+        let ipv4_address = get_value(&id, hash, "ipv4-address", &mut errors, |v| {
+            match v.parse::<Ipv4Addr>() {
+                Ok(address) if !address.is_unspecified() => Ok(address),
+                _ => Err(format!("{} is not a usable IPv4 address", v)),
+            }
+        });
+        let serial_number = get_value(&id, hash, "serial-number", &mut errors, SerialNumber::parse);
+        let architecture = get_value(&id, hash, "architecture", &mut errors, |v| {
+            Architecture::parse(v).map_err(|_| format!("{} is not a supported architecture", v))
+        });
+        let pxe = parse_value(&id, hash, "pxe", &mut errors, |v| {
+            v.clone().into_bool().map_err(|e| e.to_string())
+        });
+        let labels = parse_value(&id, hash, "labels", &mut errors, parse_labels);
+        let group = get_value(&id, hash, "group", &mut errors, |v| {
+            if v.trim().is_empty() {
+                return Err(String::from("must not be empty"));
+            }
+            Ok(v.trim().to_string())
+        });
+        let tftp_server = get_value(&id, hash, "tftp-server", &mut errors, |v| Ok(v.to_string()));
+        let power = parse_value(&id, hash, "power", &mut errors, |v| {
+            let power = if group.is_some() {
+                PowerConfig::from_partial_config(v)?
             } else {
-                false
-            },
+                PowerConfig::from_config(v)?
+            };
+            power.check_placeholders(|key| labels.as_ref().is_some_and(|l| l.contains_key(key)))?;
+            Ok(power)
+        });
+        let maintenance = parse_value(
+            &id,
+            hash,
+            "maintenance",
+            &mut errors,
+            Maintenance::from_config,
+        );
+        let capacity = match Capacity::from_config(hash) {
+            Ok(capacity) => capacity,
+            Err(e) => {
+                for (key, message) in e {
+                    errors.push(InventoryError::new(&id, &key, &message));
+                }
+                Capacity::default()
+            }
+        };
+        let mut extra = BTreeMap::new();
+        for (key, value) in hash {
+            if !KEYS.contains(&key.as_str()) && !capacity::KEYS.contains(&key.as_str()) {
+                match value.clone().try_into::<serde_json::Value>() {
+                    Ok(value) => {
+                        extra.insert(key.clone(), value);
+                    }
+                    Err(e) => errors.push(InventoryError::new(&id, key, &e.to_string())),
+                }
+            }
+        }
+        for (key, value) in &[
+            ("tftp-prefix", tftp_prefix.is_none()),
+            ("mac-address", mac_address.is_none()),
+            ("ipv4-address", ipv4_address.is_none()),
+            ("serial-number", serial_number.is_none()),
+        ] {
+            if *value && !hash.contains_key(*key) {
+                errors.push(InventoryError::new(&id, key, "is missing"));
+            }
+        }
+        match (tftp_prefix, mac_address, ipv4_address, serial_number) {
+            (Some(tftp_prefix), Some(mac_address), Some(ipv4_address), Some(serial_number))
+                if errors.is_empty() =>
+            {
+                Ok(Node {
+                    id,
+                    name: name.unwrap_or_else(|| String::from("node")),
+                    tftp_prefix,
+                    mac_address,
+                    serial_number,
+                    ipv4_address,
+                    log_inputs,
+                    architecture: architecture.unwrap_or(Architecture::ARM64),
+                    pxe: pxe.unwrap_or(false),
+                    labels: labels.unwrap_or_default(),
+                    capacity,
+                    extra,
+                    hardware: None,
+                    maintenance,
+                    groups: group.into_iter().collect(),
+                    tftp_server,
+                    power,
+                    inherited: Vec::new(),
+                })
+            }
+            _ => Err(errors),
+        }
+    }
+
+    /// Returns the labels as `key=value`, or just `key` for labels without a value.
+    #[must_use]
+    pub fn get_labels(&self) -> Vec<String> {
+        self.labels
+            .iter()
+            .map(|(key, value)| {
+                if value.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}={}", key, value)
+                }
+            })
+            .collect()
+    }
+
+    /// Returns a field by its name, such as `ipv4_address`, or else the label `key`, as used
+    /// by power command templates.
+    #[must_use]
+    pub fn get_field(&self, key: &str) -> Option<String> {
+        match key.replace('-', "_").as_str() {
+            "id" => Some(self.id.clone()),
+            "name" => Some(self.name.clone()),
+            "tftp_prefix" => Some(self.tftp_prefix.clone()),
+            "mac_address" => Some(self.mac_address.to_string()),
+            "serial_number" => Some(self.serial_number.to_string()),
+            "ipv4_address" => Some(self.ipv4_address.to_string()),
+            "architecture" => Some(self.architecture.get_name().to_string()),
+            "group" => self.get_group().map(String::from),
+            _ => self.labels.get(key).cloned(),
+        }
+    }
+
+    /// Returns the group the node was assigned to.
+    #[must_use]
+    pub fn get_group(&self) -> Option<&str> {
+        self.groups.first().map(String::as_str)
+    }
+
+    /// Returns whether the node belongs to `group` directly or through a nested group.
+    #[must_use]
+    pub fn is_in_group(&self, group: &str) -> bool {
+        self.groups.iter().any(|g| g.eq(group))
+    }
+
+    #[must_use]
+    pub fn is_inherited(&self, key: &str) -> bool {
+        self.inherited.iter().any(|k| k.eq(key))
+    }
+
+    /// Returns the power configuration without the keys inherited from the group.
+    #[must_use]
+    pub fn get_own_power(&self) -> Option<PowerConfig> {
+        let mut power = self.power.clone()?;
+        for key in &self.inherited {
+            if let Some(key) = key.strip_prefix("power.") {
+                power.remove(key);
+            }
+        }
+        Some(power)
+    }
+
+    /// Returns whether new services may be placed on the node, which is not the case while
+    /// it is in maintenance or being drained.
+    #[must_use]
+    pub fn is_schedulable(&self) -> bool {
+        self.maintenance.is_none()
+    }
+
+    /// Returns the backend that switches the power of the node.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the node has no power configuration or it is invalid
+    pub fn get_power_backend(&self) -> Result<Box<dyn PowerBackend>, String> {
+        match &self.power {
+            Some(power) => power_backend::from_config(&power.render(self)?, self),
+            None => Err(format!("node {} has no power configuration", self.id)),
+        }
+    }
+
+    /// Compares the capacity and serial number in the inventory with the hardware profile
+    /// collected from the node, if there is one.
+    #[must_use]
+    pub fn get_discrepancies(&self) -> Vec<Discrepancy> {
+        let hardware = match &self.hardware {
+            Some(hardware) => hardware,
+            None => return Vec::new(),
+        };
+        let mut discrepancies = hardware.compare(&self.capacity);
+        if let Some(serial_number) = &hardware.serial_number {
+            if !serial_number.eq(&self.serial_number) {
+                discrepancies.push(Discrepancy {
+                    key: String::from("serial-number"),
+                    claimed: self.serial_number.to_string(),
+                    reported: serial_number.to_string(),
+                });
+            }
         }
+        discrepancies
+    }
+}
+
+fn get_value<T, F>(
+    id: &str,
+    hash: &HashMap<String, Value>,
+    key: &str,
+    errors: &mut Vec<InventoryError>,
+    parse: F,
+) -> Option<T>
+where
+    F: Fn(&str) -> Result<T, String>,
+{
+    let value = hash.get(key)?;
+    match value.clone().into_str() {
+        Ok(string) => match parse(&string) {
+            Ok(parsed) => Some(parsed),
+            Err(e) => {
+                errors.push(InventoryError::new(id, key, &e));
+                None
+            }
+        },
+        Err(e) => {
+            errors.push(InventoryError::new(id, key, &e.to_string()));
+            None
+        }
+    }
+}
+
+fn parse_value<T, F>(
+    id: &str,
+    hash: &HashMap<String, Value>,
+    key: &str,
+    errors: &mut Vec<InventoryError>,
+    parse: F,
+) -> Option<T>
+where
+    F: Fn(&Value) -> Result<T, String>,
+{
+    match parse(hash.get(key)?) {
+        Ok(parsed) => Some(parsed),
+        Err(e) => {
+            errors.push(InventoryError::new(id, key, &e));
+            None
+        }
+    }
+}
+
+/// Returns whether `key` is one of [`FIELDS`], written with `-` or `_`.
+#[must_use]
+pub fn is_field(key: &str) -> bool {
+    FIELDS.contains(&key.replace('_', "-").as_str())
+}
+
+/// Accepts a table of labels or a list of `key=value` and plain `key` entries.
+fn parse_labels(value: &Value) -> Result<BTreeMap<String, String>, String> {
+    let mut labels = BTreeMap::new();
+    if let Ok(table) = value.clone().into_table() {
+        for (key, value) in table {
+            labels.insert(key, value.into_str().map_err(|e| e.to_string())?);
+        }
+        return Ok(labels);
+    }
+    for entry in value.clone().into_array().map_err(|e| e.to_string())? {
+        let entry = entry.into_str().map_err(|e| e.to_string())?;
+        match entry.split_once('=') {
+            Some((key, value)) => labels.insert(key.trim().to_string(), value.trim().to_string()),
+            None => labels.insert(entry.trim().to_string(), String::new()),
+        };
+    }
+    if labels.keys().any(String::is_empty) {
+        return Err(String::from("contains an empty label"));
+    }
+    Ok(labels)
+}
+
+fn parse_tftp_prefix(value: &str) -> Result<String, String> {
+    if value.is_empty() || value.contains(char::is_whitespace) || value.contains("..") {
+        return Err(format!("{:?} is not a valid tftp prefix", value));
     }
+    Ok(value.trim_matches('/').to_string())
 }
 
 impl PartialEq for Node {
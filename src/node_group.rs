@@ -0,0 +1,297 @@
+use std::collections::{HashMap, HashSet};
+
+use config::Value;
+use serde::{Deserialize, Serialize};
+
+use crate::architecture::Architecture;
+use crate::inventory::InventoryError;
+use crate::node::Node;
+use crate::power_config::PowerConfig;
+
+pub const KEYS: [&str; 5] = ["kind", "parent", "architecture", "tftp-server", "power"];
+
+/// Settings a group passes on to its members and subgroups unless they set them themselves.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, Eq, PartialEq)]
+pub struct GroupSettings {
+    pub architecture: Option<Architecture>,
+    pub tftp_server: Option<String>,
+    pub power: Option<PowerConfig>,
+}
+
+impl GroupSettings {
+    /// Fills every setting missing here from `other`, the power configuration key by key.
+    pub fn merge(&mut self, other: &GroupSettings) {
+        if self.architecture.is_none() {
+            self.architecture.clone_from(&other.architecture);
+        }
+        if self.tftp_server.is_none() {
+            self.tftp_server.clone_from(&other.tftp_server);
+        }
+        match (&mut self.power, &other.power) {
+            (Some(power), Some(other)) => {
+                power.merge(other);
+            }
+            (None, _) => self.power.clone_from(&other.power),
+            (Some(_), None) => {}
+        }
+    }
+}
+
+/// A site, rack, chassis or any other set of nodes. Groups nest through `parent`.
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+pub struct NodeGroup {
+    pub id: String,
+    pub kind: Option<String>,
+    pub parent: Option<String>,
+    pub settings: GroupSettings,
+}
+
+impl NodeGroup {
+    /// # Errors
+    ///
+    /// Will return `Err` with every key of `hash` that is invalid
+    pub fn from_config(
+        id: &str,
+        hash: &HashMap<String, Value>,
+    ) -> Result<Self, Vec<InventoryError>> {
+        let mut errors = Vec::new();
+        let mut group = NodeGroup {
+            id: String::from(id),
+            kind: None,
+            parent: None,
+            settings: GroupSettings::default(),
+        };
+        for (key, value) in hash {
+            let result = match key.as_str() {
+                "power" => {
+                    PowerConfig::from_partial_config(value).map(|p| group.settings.power = Some(p))
+                }
+                _ if KEYS.contains(&key.as_str()) => match value.clone().into_str() {
+                    Ok(value) => match key.as_str() {
+                        "kind" => {
+                            group.kind = Some(value);
+                            Ok(())
+                        }
+                        "parent" => {
+                            group.parent = Some(value);
+                            Ok(())
+                        }
+                        "architecture" => Architecture::parse(&value)
+                            .map(|a| group.settings.architecture = Some(a))
+                            .map_err(|_| format!("{} is not a supported architecture", value)),
+                        _ => {
+                            group.settings.tftp_server = Some(value);
+                            Ok(())
+                        }
+                    },
+                    Err(e) => Err(e.to_string()),
+                },
+                _ => Err(String::from("is not a group setting")),
+            };
+            if let Err(e) = result {
+                errors.push(group_error(id, &format!("{} {}", key, e)));
+            }
+        }
+        if errors.is_empty() {
+            Ok(group)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NodeGroups {
+    groups: Vec<NodeGroup>,
+}
+
+impl NodeGroups {
+    /// Loads every group of a table keyed by group id and checks that parents exist and do
+    /// not form a cycle.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` with every problem of every group
+    pub fn from_config(groups: &HashMap<String, Value>) -> Result<Self, Vec<InventoryError>> {
+        let mut errors = Vec::new();
+        let mut ids = groups.keys().collect::<Vec<&String>>();
+        ids.sort();
+        let mut loaded = Vec::new();
+        for id in ids {
+            match groups[id].clone().into_table() {
+                Ok(hash) => match NodeGroup::from_config(id, &hash) {
+                    Ok(group) => loaded.push(group),
+                    Err(mut e) => errors.append(&mut e),
+                },
+                Err(e) => errors.push(group_error(id, &e.to_string())),
+            }
+        }
+        let groups = NodeGroups { groups: loaded };
+        for group in &groups.groups {
+            if let Err(e) = groups.get_ancestry(&group.id) {
+                errors.push(group_error(&group.id, &format!("parent {}", e)));
+            }
+        }
+        if errors.is_empty() {
+            Ok(groups)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Checks the placeholders of the power templates of every group, which may only name
+    /// node fields or `labels`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` with every group that has an unknown placeholder
+    pub fn check_placeholders(&self, labels: &HashSet<&str>) -> Result<(), Vec<InventoryError>> {
+        let errors: Vec<InventoryError> = self
+            .groups
+            .iter()
+            .filter_map(|g| {
+                g.settings
+                    .power
+                    .as_ref()
+                    .and_then(|p| p.check_placeholders(|key| labels.contains(key)).err())
+                    .map(|e| group_error(&g.id, &format!("power {}", e)))
+            })
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    #[must_use]
+    pub fn get_groups(&self) -> Vec<NodeGroup> {
+        self.groups.clone()
+    }
+
+    #[must_use]
+    pub fn get(&self, id: &str) -> Option<&NodeGroup> {
+        self.groups.iter().find(|g| g.id.eq(id))
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Returns the ids of `id` and all its ancestors, innermost first.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if a group in the chain does not exist or the chain is a cycle
+    pub fn get_ancestry(&self, id: &str) -> Result<Vec<String>, String> {
+        let mut ancestry: Vec<String> = Vec::new();
+        let mut current = Some(id.to_string());
+        while let Some(id) = current.take() {
+            if ancestry.contains(&id) {
+                return Err(format!("{} is its own ancestor", id));
+            }
+            let group = self
+                .get(&id)
+                .ok_or_else(|| format!("group {} does not exist", id))?;
+            ancestry.push(id);
+            current.clone_from(&group.parent);
+        }
+        Ok(ancestry)
+    }
+
+    /// Returns the settings of `id` completed by those of its ancestors.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `id` or one of its ancestors does not exist
+    pub fn get_settings(&self, id: &str) -> Result<GroupSettings, String> {
+        let mut settings = GroupSettings::default();
+        for ancestor in self.get_ancestry(id)? {
+            if let Some(group) = self.get(&ancestor) {
+                settings.merge(&group.settings);
+            }
+        }
+        Ok(settings)
+    }
+
+    /// Returns the ids of the groups nested in `id`, at any depth.
+    #[must_use]
+    pub fn get_descendants(&self, id: &str) -> Vec<String> {
+        self.groups
+            .iter()
+            .filter(|g| !g.id.eq(id))
+            .filter(|g| {
+                self.get_ancestry(&g.id)
+                    .is_ok_and(|a| a.iter().any(|i| i.eq(id)))
+            })
+            .map(|g| g.id.clone())
+            .collect()
+    }
+
+    /// Resolves the group of `node` and fills the settings it does not set itself, recording
+    /// them as inherited. The architecture always has a value, so whether the node left it
+    /// open is told by `inherit_architecture`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the group of `node` does not exist
+    pub fn apply(&self, node: &mut Node, inherit_architecture: bool) -> Result<(), String> {
+        let inherit_architecture = inherit_architecture || node.is_inherited("architecture");
+        for key in node.inherited.drain(..) {
+            match key.as_str() {
+                "tftp-server" => node.tftp_server = None,
+                "power" => node.power = None,
+                _ => {
+                    if let (Some(key), Some(power)) = (key.strip_prefix("power."), &mut node.power)
+                    {
+                        power.remove(key);
+                    }
+                }
+            }
+        }
+        let group = match node.groups.first() {
+            Some(group) => group.clone(),
+            None => return Ok(()),
+        };
+        node.groups = self.get_ancestry(&group)?;
+        let settings = self.get_settings(&group)?;
+        if inherit_architecture {
+            if let Some(architecture) = settings.architecture {
+                node.architecture = architecture;
+                node.inherited.push(String::from("architecture"));
+            }
+        }
+        if node.tftp_server.is_none() && settings.tftp_server.is_some() {
+            node.tftp_server = settings.tftp_server;
+            node.inherited.push(String::from("tftp-server"));
+        }
+        match (&mut node.power, settings.power) {
+            (Some(power), Some(group_power)) => {
+                for key in power.merge(&group_power) {
+                    node.inherited.push(format!("power.{}", key));
+                }
+            }
+            (None, Some(group_power)) => {
+                node.power = Some(group_power);
+                node.inherited.push(String::from("power"));
+            }
+            (_, None) => {}
+        }
+        Ok(())
+    }
+}
+
+fn group_error(id: &str, message: &str) -> InventoryError {
+    InventoryError::new("", &format!("group {}", id), message)
+}
+
+/// Returns the nodes that belong to `group` directly or through a nested group.
+#[must_use]
+pub fn get_members(nodes: &[Node], group: &str) -> Vec<Node> {
+    nodes
+        .iter()
+        .filter(|n| n.is_in_group(group))
+        .cloned()
+        .collect()
+}
@@ -1,7 +1,9 @@
 use prettytable::Cell;
 use serde::{Deserialize, Serialize};
 
+use crate::capacity::Capacity;
 use crate::node::Node;
+use crate::node_state::{NodeLifecycle, NodeState};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct NodeRow {
@@ -14,6 +16,16 @@ pub struct NodeRow {
     pub hostname: Option<String>,
     pub ipv4_address: Option<String>,
     pub usable: bool,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub capacity: Capacity,
+    #[serde(default)]
+    pub group: Option<String>,
+    #[serde(default)]
+    pub state: Option<NodeState>,
+    #[serde(default)]
+    pub reason: Option<String>,
 }
 
 impl NodeRow {
@@ -25,19 +37,37 @@ impl NodeRow {
         ipv4_address: Option<String>,
         usable: bool,
     ) -> Self {
+        let labels = node.get_labels();
+        let group = node.get_group().map(str::to_string);
         NodeRow {
             id: node.id,
             name: node.name,
-            mac_address: node.mac_address,
+            mac_address: node.mac_address.to_string(),
             tftp_prefix: node.tftp_prefix,
-            serial_number: node.serial_number,
+            serial_number: node.serial_number.to_string(),
             status,
             hostname,
             ipv4_address,
             usable,
+            labels,
+            capacity: node.capacity,
+            group,
+            state: None,
+            reason: None,
         }
     }
 
+    /// Sets the state of the node and the reason it was entered; a node is only usable if
+    /// its state allows it.
+    #[must_use]
+    pub fn with_lifecycle(mut self, lifecycle: &NodeLifecycle) -> Self {
+        let state = lifecycle.get_state();
+        self.usable = self.usable && state.is_usable();
+        self.state = Some(state);
+        self.reason = lifecycle.get_reason();
+        self
+    }
+
     #[must_use]
     pub fn get_cells(&self) -> Vec<Cell> {
         let empty = String::from("\u{2014}");
@@ -65,4 +95,32 @@ impl NodeRow {
         ];
         vec.into_iter().map(Cell::new).collect()
     }
+
+    /// Returns the cells of `get_cells` followed by the group, state, labels and capacity of
+    /// the node.
+    #[must_use]
+    pub fn get_cells_with_details(&self) -> Vec<Cell> {
+        let mut cells = self.get_cells();
+        let state = match self.state {
+            Some(state) => match &self.reason {
+                Some(reason) => format!("{} ({})", state, reason),
+                None => state.to_string(),
+            },
+            None => String::new(),
+        };
+        let group = self.group.clone().unwrap_or_default();
+        for content in &[
+            group,
+            state,
+            self.labels.join(", "),
+            self.capacity.to_string(),
+        ] {
+            if content.is_empty() {
+                cells.push(Cell::new("\u{2014}"));
+            } else {
+                cells.push(Cell::new(content));
+            }
+        }
+        cells
+    }
 }
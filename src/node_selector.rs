@@ -0,0 +1,157 @@
+use crate::capacity;
+use crate::node::Node;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Operator {
+    Exists,
+    Missing,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Requirement {
+    pub key: String,
+    pub operator: Operator,
+    pub value: String,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub struct NodeSelector {
+    requirements: Vec<Requirement>,
+}
+
+impl NodeSelector {
+    /// Parses comma separated requirements such as `rack=rack-2,memory>=8G,disk-type=nvme,!spare`.
+    /// `architecture`, `board-model`, `cpu-cores`, `memory`, `disk` and `disk-type` refer to
+    /// the node itself, `group` matches the group of the node and the groups it is nested in,
+    /// every other key refers to its labels.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `line` could not be parsed
+    pub fn parse(line: &str) -> Result<NodeSelector, String> {
+        let mut requirements = Vec::new();
+        for part in line.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            requirements.push(parse_requirement(part)?);
+        }
+        Ok(NodeSelector { requirements })
+    }
+
+    #[must_use]
+    pub fn get_requirements(&self) -> Vec<Requirement> {
+        self.requirements.clone()
+    }
+
+    #[must_use]
+    pub fn matches(&self, node: &Node) -> bool {
+        self.requirements.iter().all(|r| r.matches(node))
+    }
+
+    #[must_use]
+    pub fn select(&self, nodes: &[Node]) -> Vec<Node> {
+        nodes.iter().filter(|n| self.matches(n)).cloned().collect()
+    }
+}
+
+impl Requirement {
+    #[must_use]
+    pub fn matches(&self, node: &Node) -> bool {
+        if self.key == "group" {
+            return match self.operator {
+                Operator::Exists => !node.groups.is_empty(),
+                Operator::Missing => node.groups.is_empty(),
+                Operator::Equal => node.is_in_group(&self.value),
+                Operator::NotEqual => !node.is_in_group(&self.value),
+                _ => false,
+            };
+        }
+        let actual = get_attribute(node, &self.key);
+        match self.operator {
+            Operator::Exists => actual.is_some(),
+            Operator::Missing => actual.is_none(),
+            Operator::Equal => actual.is_some_and(|a| a.eq_ignore_ascii_case(&self.value)),
+            Operator::NotEqual => !actual.is_some_and(|a| a.eq_ignore_ascii_case(&self.value)),
+            _ => match (
+                actual.and_then(|a| to_number(&self.key, &a)),
+                to_number(&self.key, &self.value),
+            ) {
+                (Some(actual), Some(expected)) => match self.operator {
+                    Operator::Greater => actual > expected,
+                    Operator::GreaterOrEqual => actual >= expected,
+                    Operator::Less => actual < expected,
+                    _ => actual <= expected,
+                },
+                _ => false,
+            },
+        }
+    }
+}
+
+fn parse_requirement(part: &str) -> Result<Requirement, String> {
+    if let Some(key) = part.strip_prefix('!') {
+        return requirement(key, Operator::Missing, "", part);
+    }
+    for (token, operator) in &[
+        ("!=", Operator::NotEqual),
+        (">=", Operator::GreaterOrEqual),
+        ("<=", Operator::LessOrEqual),
+        ("=", Operator::Equal),
+        (">", Operator::Greater),
+        ("<", Operator::Less),
+    ] {
+        if let Some((key, value)) = part.split_once(token) {
+            if value.trim().is_empty() {
+                return Err(format!("{} is missing a value", part));
+            }
+            let requirement = requirement(key, *operator, value, part)?;
+            let numeric = !matches!(operator, Operator::Equal | Operator::NotEqual);
+            if numeric && to_number(&requirement.key, &requirement.value).is_none() {
+                return Err(format!("{} compares a non-numeric value", part));
+            }
+            return Ok(requirement);
+        }
+    }
+    requirement(part, Operator::Exists, "", part)
+}
+
+fn requirement(
+    key: &str,
+    operator: Operator,
+    value: &str,
+    part: &str,
+) -> Result<Requirement, String> {
+    let key = key.trim();
+    if key.is_empty() || key.contains(|c: char| c.is_whitespace() || "!=<>".contains(c)) {
+        return Err(format!("{} is not a valid requirement", part));
+    }
+    Ok(Requirement {
+        key: key.to_string(),
+        operator,
+        value: value.trim().to_string(),
+    })
+}
+
+fn get_attribute(node: &Node, key: &str) -> Option<String> {
+    let capacity = &node.capacity;
+    match key {
+        "architecture" => Some(node.architecture.get_name().to_string()),
+        "board-model" => capacity.board_model.clone(),
+        "cpu-cores" => capacity.cpu_cores.map(|c| c.to_string()),
+        "memory" => capacity.memory_mb.map(|m| m.to_string()),
+        "disk" => capacity.disk_mb.map(|d| d.to_string()),
+        "disk-type" => capacity.disk_type.clone(),
+        _ => node.labels.get(key).cloned(),
+    }
+}
+
+fn to_number(key: &str, value: &str) -> Option<u64> {
+    match key {
+        "memory" | "disk" => capacity::parse_size(value).ok(),
+        _ => value.trim().parse::<u64>().ok(),
+    }
+}
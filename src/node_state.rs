@@ -0,0 +1,252 @@
+use std::fmt;
+
+use chrono::{NaiveDateTime, Utc};
+use rusqlite::Row;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Hash, Default)]
+pub enum NodeState {
+    #[default]
+    Available = 0,
+    Reserved = 1,
+    PoweringOn = 2,
+    Booting = 3,
+    Running = 4,
+    Failed = 5,
+    Maintenance = 6,
+    Draining = 7,
+}
+
+impl NodeState {
+    #[must_use]
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            NodeState::Available => "available",
+            NodeState::Reserved => "reserved",
+            NodeState::PoweringOn => "powering-on",
+            NodeState::Booting => "booting",
+            NodeState::Running => "running",
+            NodeState::Failed => "failed",
+            NodeState::Maintenance => "maintenance",
+            NodeState::Draining => "draining",
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if `line` could not be parsed
+    pub fn parse(line: &str) -> Result<NodeState, &'static str> {
+        match line.to_lowercase().as_str() {
+            "available" => Ok(NodeState::Available),
+            "reserved" => Ok(NodeState::Reserved),
+            "powering-on" | "poweringon" => Ok(NodeState::PoweringOn),
+            "booting" => Ok(NodeState::Booting),
+            "running" => Ok(NodeState::Running),
+            "failed" => Ok(NodeState::Failed),
+            "maintenance" => Ok(NodeState::Maintenance),
+            "draining" => Ok(NodeState::Draining),
+            _ => Err("not a node state"),
+        }
+    }
+
+    /// Returns the states a node in this state may move to. Any state may fail.
+    #[must_use]
+    pub fn get_successors(&self) -> Vec<NodeState> {
+        let mut successors = match self {
+            NodeState::Available => vec![
+                NodeState::Reserved,
+                NodeState::PoweringOn,
+                NodeState::Maintenance,
+            ],
+            NodeState::PoweringOn => vec![NodeState::Booting, NodeState::Available],
+            NodeState::Booting => vec![NodeState::Running, NodeState::Available],
+            NodeState::Running => vec![
+                NodeState::Available,
+                NodeState::Reserved,
+                NodeState::PoweringOn,
+                NodeState::Draining,
+            ],
+            NodeState::Reserved | NodeState::Failed => vec![
+                NodeState::Available,
+                NodeState::PoweringOn,
+                NodeState::Maintenance,
+            ],
+            NodeState::Maintenance => vec![NodeState::Available],
+            NodeState::Draining => vec![NodeState::Maintenance, NodeState::Available],
+        };
+        if !self.eq(&NodeState::Failed) {
+            successors.push(NodeState::Failed);
+        }
+        successors
+    }
+
+    #[must_use]
+    pub fn can_transition_to(&self, state: NodeState) -> bool {
+        self.get_successors().contains(&state)
+    }
+
+    /// Returns whether new work may be scheduled on a node in this state.
+    #[must_use]
+    pub fn is_usable(&self) -> bool {
+        matches!(self, NodeState::Available | NodeState::Running)
+    }
+}
+
+impl fmt::Display for NodeState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.get_name())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+pub struct Transition {
+    pub node: String,
+    pub from: NodeState,
+    pub to: NodeState,
+    pub at: NaiveDateTime,
+    pub reason: String,
+}
+
+impl Transition {
+    /// Expects the columns `node`, `from`, `to`, `at` and `reason`, with both states stored
+    /// as integers.
+    #[must_use]
+    pub fn from_row(row: &Row) -> Option<Self> {
+        if row.column_count() >= 5 {
+            return Some(Transition {
+                node: row.get(0).unwrap_or_default(),
+                from: from_db_to_state(row.get(1).unwrap_or(usize::MIN)),
+                to: from_db_to_state(row.get(2).unwrap_or(usize::MIN)),
+                at: row.get(3).unwrap_or_else(|_| Utc::now().naive_utc()),
+                reason: row.get(4).unwrap_or_default(),
+            });
+        }
+        None
+    }
+}
+
+impl fmt::Display for Transition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}: {} -> {}",
+            self.at.format("%Y-%m-%d %H:%M:%S"),
+            self.node,
+            self.from,
+            self.to
+        )?;
+        if !self.reason.is_empty() {
+            write!(f, " ({})", self.reason)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NodeLifecycle {
+    pub node: String,
+    state: NodeState,
+    history: Vec<Transition>,
+}
+
+impl NodeLifecycle {
+    #[must_use]
+    pub fn new(node: &str) -> Self {
+        NodeLifecycle {
+            node: String::from(node),
+            state: NodeState::Available,
+            history: Vec::new(),
+        }
+    }
+
+    /// Restores the lifecycle of `node` from its stored transitions.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if a transition belongs to another node or does not continue from
+    /// the state the previous one ended in
+    pub fn from_history(node: &str, transitions: Vec<Transition>) -> Result<Self, String> {
+        let mut lifecycle = NodeLifecycle::new(node);
+        let mut transitions = transitions;
+        transitions.sort_by_key(|t| t.at);
+        for transition in transitions {
+            if !transition.node.eq(node) {
+                return Err(format!(
+                    "transition of node {} can not be applied to node {}",
+                    transition.node, node
+                ));
+            }
+            if !transition.from.eq(&lifecycle.state) {
+                return Err(format!(
+                    "node {} is {} but the transition at {} starts from {}",
+                    node, lifecycle.state, transition.at, transition.from
+                ));
+            }
+            lifecycle.state = transition.to;
+            lifecycle.history.push(transition);
+        }
+        Ok(lifecycle)
+    }
+
+    #[must_use]
+    pub fn get_state(&self) -> NodeState {
+        self.state
+    }
+
+    #[must_use]
+    pub fn get_history(&self) -> Vec<Transition> {
+        self.history.clone()
+    }
+
+    #[must_use]
+    pub fn get_last_transition(&self) -> Option<&Transition> {
+        self.history.last()
+    }
+
+    /// Returns the reason of the transition into the current state, which tells why a node
+    /// is not usable.
+    #[must_use]
+    pub fn get_reason(&self) -> Option<String> {
+        self.get_last_transition()
+            .map(|t| t.reason.clone())
+            .filter(|r| !r.is_empty())
+    }
+
+    /// Moves the node to `state` and records the transition.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the current state can not move to `state`
+    pub fn transition(&mut self, state: NodeState, reason: &str) -> Result<Transition, String> {
+        if !self.state.can_transition_to(state) {
+            return Err(format!(
+                "node {} can not change from {} to {}",
+                self.node, self.state, state
+            ));
+        }
+        let transition = Transition {
+            node: self.node.clone(),
+            from: self.state,
+            to: state,
+            at: Utc::now().naive_utc(),
+            reason: String::from(reason),
+        };
+        self.state = state;
+        self.history.push(transition.clone());
+        Ok(transition)
+    }
+}
+
+#[must_use]
+pub fn from_db_to_state(id: usize) -> NodeState {
+    match id {
+        1 => NodeState::Reserved,
+        2 => NodeState::PoweringOn,
+        3 => NodeState::Booting,
+        4 => NodeState::Running,
+        5 => NodeState::Failed,
+        6 => NodeState::Maintenance,
+        7 => NodeState::Draining,
+        _ => NodeState::Available,
+    }
+}
@@ -1,10 +1,186 @@
-use std::process::Command;
+use std::fmt;
+use std::io::ErrorKind;
+use std::time::{Duration, Instant};
 
-#[derive(Debug, Eq, PartialEq, Clone)]
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::command_runner;
+use crate::command_runner::CommandRunner;
+use crate::dry_run;
+use crate::dry_run::Effect;
+
+/// How long a power command may run before it is killed, unless configured otherwise.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
 pub enum Type {
     ON,
     OFF,
     REBOOT,
+    STATUS,
+}
+
+impl Type {
+    #[must_use]
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Type::ON => "on",
+            Type::OFF => "off",
+            Type::REBOOT => "reboot",
+            Type::STATUS => "status",
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if `line` could not be parsed
+    pub fn parse(line: &str) -> Result<Type, String> {
+        match line.to_lowercase().as_str() {
+            "on" => Ok(Type::ON),
+            "off" => Ok(Type::OFF),
+            "reboot" => Ok(Type::REBOOT),
+            "status" => Ok(Type::STATUS),
+            _ => Err(format!("{} is not a power action", line)),
+        }
+    }
+}
+
+/// The power state of a node as reported by its power backend.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum PowerState {
+    On,
+    Off,
+    Unknown,
+}
+
+impl PowerState {
+    /// Reads the state from the output of a status command such as `Chassis Power is on`,
+    /// taking the last word that is a state.
+    #[must_use]
+    pub fn parse(output: &str) -> PowerState {
+        output
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .rev()
+            .find_map(|word| match word.to_lowercase().as_str() {
+                "on" | "true" | "1" => Some(PowerState::On),
+                "off" | "false" | "0" => Some(PowerState::Off),
+                _ => None,
+            })
+            .unwrap_or(PowerState::Unknown)
+    }
+}
+
+impl fmt::Display for PowerState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PowerState::On => write!(f, "on"),
+            PowerState::Off => write!(f, "off"),
+            PowerState::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// What happened when a power action was performed: the exit code and output of a command,
+/// or the answer of a device, when it started, how long it took and after how many attempts.
+#[derive(Debug, Clone)]
+pub struct PowerResult {
+    pub action: Type,
+    pub target: String,
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub started: NaiveDateTime,
+    pub duration: Duration,
+    pub attempts: u32,
+    pub timed_out: bool,
+    pub error: Option<String>,
+    /// Problems that did not make the action fail, such as an audit log it could not be
+    /// recorded in.
+    pub warnings: Vec<String>,
+}
+
+impl PowerResult {
+    #[must_use]
+    pub fn new(action: Type, target: &str) -> Self {
+        PowerResult {
+            action,
+            target: String::from(target),
+            exit_code: None,
+            stdout: String::new(),
+            stderr: String::new(),
+            started: Utc::now().naive_utc(),
+            duration: Duration::default(),
+            attempts: 1,
+            timed_out: false,
+            error: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Runs `perform`, timing it and keeping its output or error. In dry-run anything but a
+    /// status query is recorded and reported as successful instead.
+    pub fn measure<F>(action: Type, target: &str, perform: F) -> Self
+    where
+        F: FnOnce() -> Result<String, String>,
+    {
+        let mut result = PowerResult::new(action, target);
+        if result.action != Type::STATUS
+            && dry_run::record(Effect::Power {
+                action: result.action.get_name().to_string(),
+                target: String::from(target),
+            })
+        {
+            return result;
+        }
+        let started = Instant::now();
+        match perform() {
+            Ok(stdout) => result.stdout = stdout,
+            Err(e) => result.error = Some(e),
+        }
+        result.duration = started.elapsed();
+        result
+    }
+
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Returns the output on success.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` with the reason and any error output if the action failed
+    pub fn into_result(self) -> Result<String, String> {
+        if self.is_success() {
+            Ok(self.stdout)
+        } else {
+            Err(self.to_string())
+        }
+    }
+}
+
+impl fmt::Display for PowerResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} {} ", self.action, self.target)?;
+        match &self.error {
+            None => write!(f, "succeeded")?,
+            Some(error) => write!(f, "failed: {}", error)?,
+        }
+        write!(f, " after {} ms", self.duration.as_millis())?;
+        if self.attempts > 1 {
+            write!(f, " on attempt {}", self.attempts)?;
+        }
+        let stderr = self.stderr.trim();
+        if !self.is_success() && !stderr.is_empty() {
+            write!(f, "\n{}", stderr)?;
+        }
+        for warning in &self.warnings {
+            write!(f, "\nwarning: {}", warning)?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -15,6 +191,15 @@ pub struct PowerAction {
 }
 
 impl PowerAction {
+    #[must_use]
+    pub fn new(action: Type, command: &str, arguments: Vec<String>) -> Self {
+        PowerAction {
+            action,
+            command: String::from(command),
+            arguments,
+        }
+    }
+
     /// # Errors
     ///
     /// Will return `Err` if `command` could not be parsed
@@ -50,12 +235,112 @@ impl PowerAction {
     }
 
     #[must_use]
-    pub fn execute(self) -> bool {
-        if let Ok(child) = Command::new(self.command).args(self.arguments).spawn() {
-            if let Ok(result) = child.wait_with_output() {
-                return result.status.success();
+    pub fn get_arguments(&self) -> Vec<String> {
+        self.arguments.clone()
+    }
+
+    /// Runs the command, killing it once `timeout` has passed, and captures its output. In
+    /// dry-run anything but a status query is recorded and reported as successful instead.
+    #[must_use]
+    pub fn execute(&self, timeout: Duration) -> PowerResult {
+        self.execute_with(&*command_runner::get_runner(), timeout)
+    }
+
+    /// Like [`PowerAction::execute`], but runs the command with `runner`.
+    #[must_use]
+    pub fn execute_with(&self, runner: &dyn CommandRunner, timeout: Duration) -> PowerResult {
+        let mut line = vec![self.command.clone()];
+        line.extend(self.arguments.iter().cloned());
+        let mut result = PowerResult::new(self.action.clone(), &line.join(" "));
+        if self.action != Type::STATUS
+            && dry_run::record(Effect::Command {
+                program: self.command.clone(),
+                arguments: self.arguments.clone(),
+            })
+        {
+            return result;
+        }
+        let started = Instant::now();
+        match runner.run(&self.command, &self.arguments, Some(timeout)) {
+            Ok(output) => {
+                if !output.is_success() {
+                    result.error = Some(match output.code {
+                        Some(code) => format!("exited with {}", code),
+                        None => String::from("was killed by a signal"),
+                    });
+                }
+                result.exit_code = output.code;
+                result.stdout = output.stdout;
+                result.stderr = output.stderr;
             }
+            Err(e) => {
+                result.timed_out = e.kind() == ErrorKind::TimedOut;
+                result.error = Some(e.to_string());
+            }
+        }
+        result.duration = started.elapsed();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+    use crate::command_runner::{CommandOutput, RecordingRunner};
+
+    /// Fails every command as if it had not exited in time.
+    #[derive(Debug)]
+    struct SlowRunner;
+
+    impl CommandRunner for SlowRunner {
+        fn run(
+            &self,
+            _program: &str,
+            _arguments: &[String],
+            _timeout: Option<Duration>,
+        ) -> io::Result<CommandOutput> {
+            Err(io::Error::new(ErrorKind::TimedOut, "did not exit in time"))
+        }
+
+        fn get_name(&self) -> String {
+            String::from("slow")
         }
-        false
+    }
+
+    #[test]
+    fn execute_with_runs_the_command_with_the_runner() {
+        let runner = RecordingRunner::new().with_output(
+            "ipmitool",
+            CommandOutput::new(0, "Chassis Power Control: Up/On\n"),
+        );
+        let action = PowerAction::parse(Type::ON, "ipmitool -H 10.0.0.1 power on").unwrap();
+        let result = action.execute_with(&runner, DEFAULT_TIMEOUT);
+        assert!(result.is_success());
+        assert_eq!(result.exit_code, Some(0));
+        assert_eq!(result.stdout, "Chassis Power Control: Up/On\n");
+        assert_eq!(result.target, "ipmitool -H 10.0.0.1 power on");
+        assert_eq!(
+            runner.get_calls(),
+            vec![vec!["ipmitool", "-H", "10.0.0.1", "power", "on"]]
+        );
+    }
+
+    #[test]
+    fn execute_with_reports_failures() {
+        let mut failed = CommandOutput::new(3, "");
+        failed.stderr = String::from("no route to host");
+        let runner = RecordingRunner::new().with_output("ipmitool", failed);
+        let action = PowerAction::parse(Type::OFF, "ipmitool power off").unwrap();
+        let result = action.execute_with(&runner, DEFAULT_TIMEOUT);
+        assert_eq!(result.error.as_deref(), Some("exited with 3"));
+        assert_eq!(result.stderr, "no route to host");
+        assert!(!result.timed_out);
+
+        let result = action.execute_with(&SlowRunner, DEFAULT_TIMEOUT);
+        assert!(!result.is_success());
+        assert!(result.timed_out);
+        assert_eq!(result.exit_code, None);
     }
 }
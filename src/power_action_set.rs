@@ -1,10 +1,17 @@
-use crate::power_action::{PowerAction, Type};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::command_runner::CommandRunner;
+use crate::power_action::{PowerAction, Type, DEFAULT_TIMEOUT};
 
 #[derive(Debug, Clone)]
 pub struct PowerActionSet {
     on: Result<PowerAction, String>,
     off: Result<PowerAction, String>,
     reboot: Result<PowerAction, String>,
+    status: Result<PowerAction, String>,
+    timeout: Duration,
+    runner: Option<Arc<dyn CommandRunner>>,
 }
 
 impl PowerActionSet {
@@ -14,7 +21,43 @@ impl PowerActionSet {
         off: Result<PowerAction, String>,
         reboot: Result<PowerAction, String>,
     ) -> Self {
-        PowerActionSet { on, off, reboot }
+        PowerActionSet {
+            on,
+            off,
+            reboot,
+            status: Err(String::from("no status command is configured")),
+            timeout: DEFAULT_TIMEOUT,
+            runner: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_status(mut self, status: Result<PowerAction, String>) -> Self {
+        self.status = status;
+        self
+    }
+
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    #[must_use]
+    pub fn get_timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Runs the commands with `runner` instead of the global one.
+    #[must_use]
+    pub fn with_runner(mut self, runner: Option<Arc<dyn CommandRunner>>) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    #[must_use]
+    pub fn get_runner(&self) -> Option<Arc<dyn CommandRunner>> {
+        self.runner.clone()
     }
 
     /// # Errors
@@ -25,6 +68,7 @@ impl PowerActionSet {
             crate::power_action::Type::ON => self.on,
             crate::power_action::Type::OFF => self.off,
             crate::power_action::Type::REBOOT => self.reboot,
+            crate::power_action::Type::STATUS => self.status,
         }
     }
 }
@@ -0,0 +1,328 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::dry_run;
+use crate::power_action::{PowerResult, PowerState, Type};
+use crate::power_backend::PowerBackend;
+use crate::power_batch::BatchReport;
+use crate::retry_policy::RetryPolicy;
+
+/// Where the actions of every power backend created from now on are recorded; `None` while
+/// nothing is audited.
+static SINK: Mutex<Option<AuditSink>> = Mutex::new(None);
+
+/// Who asked for a power action.
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+pub enum Invoker {
+    User(String),
+    Deployment(i64),
+    System,
+}
+
+impl fmt::Display for Invoker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Invoker::User(user) => write!(f, "user {}", user),
+            Invoker::Deployment(id) => write!(f, "deployment {}", id),
+            Invoker::System => write!(f, "system"),
+        }
+    }
+}
+
+/// A power action performed on a node, as kept in the audit log.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PowerAuditEntry {
+    pub node: String,
+    pub action: Type,
+    pub backend: String,
+    pub invoker: Invoker,
+    pub at: NaiveDateTime,
+    pub success: bool,
+    pub exit_code: Option<i32>,
+    pub output: String,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+    pub attempts: u32,
+}
+
+impl PowerAuditEntry {
+    /// Records `result` at the time it started, with its output, standard error following
+    /// standard output.
+    #[must_use]
+    pub fn new(node: &str, backend: &str, invoker: Invoker, result: &PowerResult) -> Self {
+        let output = [result.stdout.trim(), result.stderr.trim()]
+            .iter()
+            .filter(|o| !o.is_empty())
+            .copied()
+            .collect::<Vec<&str>>()
+            .join("\n");
+        PowerAuditEntry {
+            node: String::from(node),
+            action: result.action.clone(),
+            backend: String::from(backend),
+            invoker,
+            at: result.started,
+            success: result.is_success(),
+            exit_code: result.exit_code,
+            output,
+            error: result.error.clone(),
+            duration_ms: u64::try_from(result.duration.as_millis()).unwrap_or(u64::MAX),
+            attempts: result.attempts,
+        }
+    }
+}
+
+impl fmt::Display for PowerAuditEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} by {} via {}: ",
+            self.at.format("%Y-%m-%d %H:%M:%S"),
+            self.action.get_name(),
+            self.node,
+            self.invoker,
+            self.backend
+        )?;
+        match &self.error {
+            None => write!(f, "succeeded")?,
+            Some(error) => write!(f, "failed: {}", error)?,
+        }
+        write!(f, " in {} ms", self.duration_ms)
+    }
+}
+
+/// The power actions performed so far, oldest first. When opened from a file, every recorded
+/// entry is appended to it as a line of JSON, the only form the log is stored in.
+#[derive(Debug, Clone, Default)]
+pub struct PowerAudit {
+    filename: Option<String>,
+    entries: Vec<PowerAuditEntry>,
+}
+
+impl PowerAudit {
+    #[must_use]
+    pub fn new(entries: Vec<PowerAuditEntry>) -> Self {
+        PowerAudit {
+            filename: None,
+            entries,
+        }
+    }
+
+    /// Loads the log kept in `filename`, which is created on the first record.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `filename` exists but could not be read or parsed
+    pub fn open(filename: &str) -> io::Result<Self> {
+        let content = match fs::read_to_string(filename) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        let mut entries = Vec::new();
+        for line in content.lines().filter(|l| !l.trim().is_empty()) {
+            entries.push(
+                serde_json::from_str(line)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            );
+        }
+        Ok(PowerAudit {
+            filename: Some(String::from(filename)),
+            entries,
+        })
+    }
+
+    #[must_use]
+    pub fn get_entries(&self) -> Vec<PowerAuditEntry> {
+        self.entries.clone()
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if the entry could not be appended to the file of the log
+    pub fn record(&mut self, entry: PowerAuditEntry) -> io::Result<()> {
+        if let Some(filename) = &self.filename {
+            let line = serde_json::to_string(&entry)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(filename)?;
+            writeln!(file, "{}", line)?;
+        }
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Records every action of a batch run while no [`AuditSink`] was set, each at the time
+    /// it started.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an entry could not be appended to the file of the log
+    pub fn record_batch(&mut self, report: &BatchReport, invoker: &Invoker) -> io::Result<()> {
+        for batch_result in &report.results {
+            for result in &batch_result.results {
+                self.record(PowerAuditEntry::new(
+                    &batch_result.node,
+                    &batch_result.backend,
+                    invoker.clone(),
+                    result,
+                ))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the last `count` actions on `node`, newest first.
+    #[must_use]
+    pub fn get_last(&self, node: &str, count: usize) -> Vec<PowerAuditEntry> {
+        self.entries
+            .iter()
+            .rev()
+            .filter(|e| e.node.eq(node))
+            .take(count)
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the actions from `start` up to but excluding `end`, oldest first.
+    #[must_use]
+    pub fn get_between(&self, start: NaiveDateTime, end: NaiveDateTime) -> Vec<PowerAuditEntry> {
+        self.entries
+            .iter()
+            .filter(|e| start <= e.at && e.at < end)
+            .cloned()
+            .collect()
+    }
+
+    #[must_use]
+    pub fn get_by_invoker(&self, invoker: &Invoker) -> Vec<PowerAuditEntry> {
+        self.entries
+            .iter()
+            .filter(|e| e.invoker.eq(invoker))
+            .cloned()
+            .collect()
+    }
+
+    #[must_use]
+    pub fn get_failures(&self, node: &str) -> Vec<PowerAuditEntry> {
+        self.entries
+            .iter()
+            .filter(|e| e.node.eq(node) && !e.success)
+            .cloned()
+            .collect()
+    }
+}
+
+/// A log that power actions are recorded in as they are performed, on behalf of `invoker`.
+#[derive(Debug, Clone)]
+pub struct AuditSink {
+    pub audit: Arc<Mutex<PowerAudit>>,
+    pub invoker: Invoker,
+}
+
+impl AuditSink {
+    #[must_use]
+    pub fn new(audit: Arc<Mutex<PowerAudit>>, invoker: Invoker) -> Self {
+        AuditSink { audit, invoker }
+    }
+
+    /// Records `result` unless it was only previewed in dry-run.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the entry could not be appended to the file of the log
+    pub fn record(&self, node: &str, backend: &str, result: &PowerResult) -> io::Result<()> {
+        if dry_run::is_enabled() {
+            return Ok(());
+        }
+        self.audit
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .record(PowerAuditEntry::new(
+                node,
+                backend,
+                self.invoker.clone(),
+                result,
+            ))
+    }
+}
+
+/// Records the actions of every power backend created from now on in `sink`.
+pub fn set_sink(sink: AuditSink) {
+    *SINK.lock().unwrap_or_else(PoisonError::into_inner) = Some(sink);
+}
+
+/// Stops recording the actions of power backends created from now on.
+pub fn reset_sink() {
+    *SINK.lock().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+#[must_use]
+pub fn get_sink() -> Option<AuditSink> {
+    SINK.lock().unwrap_or_else(PoisonError::into_inner).clone()
+}
+
+/// Wraps `backend` so that its actions on `node` are recorded in the current sink, if one
+/// is set.
+#[must_use]
+pub fn audit(backend: Box<dyn PowerBackend>, node: &str) -> Box<dyn PowerBackend> {
+    match get_sink() {
+        Some(sink) => Box::new(AuditedBackend {
+            backend,
+            node: String::from(node),
+            sink,
+        }),
+        None => backend,
+    }
+}
+
+/// A backend that records every action it performs, retried actions once.
+#[derive(Debug)]
+pub struct AuditedBackend {
+    backend: Box<dyn PowerBackend>,
+    node: String,
+    sink: AuditSink,
+}
+
+impl AuditedBackend {
+    /// Records `result`, adding a warning to it if the audit log could not be written.
+    fn record(&self, mut result: PowerResult) -> PowerResult {
+        if let Err(e) = self
+            .sink
+            .record(&self.node, self.backend.get_name(), &result)
+        {
+            result
+                .warnings
+                .push(format!("could not be recorded in the audit log: {}", e));
+        }
+        result
+    }
+}
+
+impl PowerBackend for AuditedBackend {
+    fn run(&self, action: &Type) -> PowerResult {
+        self.record(self.backend.run(action))
+    }
+
+    fn get_state(&self) -> Result<PowerState, String> {
+        self.backend.get_state()
+    }
+
+    fn get_name(&self) -> &'static str {
+        self.backend.get_name()
+    }
+
+    fn run_with_retries(&self, action: &Type, policy: &RetryPolicy) -> PowerResult {
+        self.record(self.backend.run_with_retries(action, policy))
+    }
+}
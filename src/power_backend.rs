@@ -0,0 +1,785 @@
+use std::fmt;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::command_runner::CommandRunner;
+use crate::mac_address::MacAddress;
+use crate::node::Node;
+use crate::power_action;
+use crate::power_action::{PowerAction, PowerResult, PowerState, Type};
+use crate::power_action_set::PowerActionSet;
+use crate::power_audit;
+use crate::power_config::{Backend, PowerConfig};
+use crate::retry_policy::RetryPolicy;
+
+/// How long to wait for an answer from a device over the network.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_REDFISH_SYSTEM: &str = "/redfish/v1/Systems/1";
+const DEFAULT_BROADCAST: &str = "255.255.255.255:9";
+/// The number of relays Tasmota supports, which is more than any Shelly device has.
+const MAX_RELAYS: u8 = 32;
+
+/// Something that can switch the power of a node.
+pub trait PowerBackend: fmt::Debug + Send {
+    /// Performs `action` once and reports what happened.
+    fn run(&self, action: &Type) -> PowerResult;
+
+    /// # Errors
+    ///
+    /// Will return `Err` if the backend could not be asked for the state
+    fn get_state(&self) -> Result<PowerState, String>;
+
+    fn get_name(&self) -> &'static str;
+
+    /// # Errors
+    ///
+    /// Will return `Err` if the action is not supported or did not succeed
+    fn execute(&self, action: &Type) -> Result<(), String> {
+        self.run(action).into_result().map(|_| ())
+    }
+
+    /// Performs `action` until it succeeds or `policy` gives up.
+    fn run_with_retries(&self, action: &Type, policy: &RetryPolicy) -> PowerResult {
+        policy.run(|| self.run(action))
+    }
+
+    /// Switches the node on unless it is known to be on already, and returns whether it
+    /// had to be switched.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if switching the node on did not succeed
+    fn ensure_on(&self) -> Result<bool, String> {
+        ensure(self, PowerState::On, &Type::ON)
+    }
+
+    /// Switches the node off unless it is known to be off already, and returns whether it
+    /// had to be switched.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if switching the node off did not succeed
+    fn ensure_off(&self) -> Result<bool, String> {
+        ensure(self, PowerState::Off, &Type::OFF)
+    }
+}
+
+/// Performs `action` unless the node is in `state` already. A state that cannot be told is
+/// treated as different.
+fn ensure<B: PowerBackend + ?Sized>(
+    backend: &B,
+    state: PowerState,
+    action: &Type,
+) -> Result<bool, String> {
+    if backend.get_state().is_ok_and(|s| s == state) {
+        return Ok(false);
+    }
+    backend.execute(action)?;
+    Ok(true)
+}
+
+impl PowerBackend for PowerActionSet {
+    fn run(&self, action: &Type) -> PowerResult {
+        match self.clone().get(action) {
+            Ok(power_action) => match self.get_runner() {
+                Some(runner) => power_action.execute_with(&*runner, self.get_timeout()),
+                None => power_action.execute(self.get_timeout()),
+            },
+            Err(e) => PowerResult::measure(action.clone(), self.get_name(), || Err(e)),
+        }
+    }
+
+    fn get_state(&self) -> Result<PowerState, String> {
+        let output = self.run(&Type::STATUS).into_result()?;
+        Ok(PowerState::parse(&output))
+    }
+
+    fn get_name(&self) -> &'static str {
+        Backend::Command.get_name()
+    }
+}
+
+/// Resets a system through the Redfish API of its BMC. Only plain HTTP is spoken, so put a
+/// TLS terminating proxy in front of BMCs that insist on HTTPS. The `username` and `password`
+/// are sent as Basic authentication, which anyone on the network path can read; keep the BMC
+/// or the proxy on a trusted management network.
+#[derive(Debug, Clone)]
+pub struct RedfishBackend {
+    pub address: String,
+    pub system: String,
+    pub username: String,
+    pub password: String,
+    pub timeout: Duration,
+}
+
+impl RedfishBackend {
+    fn get_reset_type(action: &Type) -> Option<&'static str> {
+        match action {
+            Type::ON => Some("On"),
+            Type::OFF => Some("ForceOff"),
+            Type::REBOOT => Some("ForceRestart"),
+            Type::STATUS => None,
+        }
+    }
+
+    fn request(&self, method: &str, path: &str, body: Option<&str>) -> Result<String, String> {
+        let auth = format!("{}:{}", self.username, self.password);
+        let (status, response) =
+            http_request(&self.address, method, path, Some(&auth), body, self.timeout)?;
+        if (200..300).contains(&status) {
+            Ok(response)
+        } else {
+            Err(format!(
+                "redfish {} returned {}: {}",
+                self.address,
+                status,
+                response.trim()
+            ))
+        }
+    }
+}
+
+impl PowerBackend for RedfishBackend {
+    fn run(&self, action: &Type) -> PowerResult {
+        PowerResult::measure(action.clone(), &self.address, || {
+            let reset_type = match RedfishBackend::get_reset_type(action) {
+                Some(reset_type) => reset_type,
+                None => return self.get_state().map(|s| s.to_string()),
+            };
+            let path = format!(
+                "{}/Actions/ComputerSystem.Reset",
+                self.system.trim_end_matches('/')
+            );
+            let body = format!("{{\"ResetType\":\"{}\"}}", reset_type);
+            self.request("POST", &path, Some(&body))
+        })
+    }
+
+    /// Reads `PowerState` of the system; transitional states such as `PoweringOn` are
+    /// unknown.
+    fn get_state(&self) -> Result<PowerState, String> {
+        let response = self.request("GET", &self.system, None)?;
+        let system: serde_json::Value = serde_json::from_str(&response)
+            .map_err(|e| format!("redfish {} sent invalid JSON: {}", self.address, e))?;
+        Ok(match system["PowerState"].as_str() {
+            Some("On") => PowerState::On,
+            Some("Off") => PowerState::Off,
+            _ => PowerState::Unknown,
+        })
+    }
+
+    fn get_name(&self) -> &'static str {
+        Backend::Redfish.get_name()
+    }
+}
+
+/// Switches a relay of a smart plug with Tasmota or Shelly firmware. Rebooting switches the
+/// relay off and on again after `delay`.
+#[derive(Debug, Clone)]
+pub struct RelayBackend {
+    pub backend: Backend,
+    pub address: String,
+    pub relay: u8,
+    pub delay: Duration,
+    pub timeout: Duration,
+}
+
+impl RelayBackend {
+    fn get_status_path(&self) -> String {
+        match self.backend {
+            Backend::Shelly => format!("/relay/{}", self.relay),
+            _ => format!("/cm?cmnd=Power{}", u16::from(self.relay) + 1),
+        }
+    }
+
+    fn get_path(&self, on: bool) -> String {
+        match self.backend {
+            Backend::Shelly => format!(
+                "/relay/{}?turn={}",
+                self.relay,
+                if on { "on" } else { "off" }
+            ),
+            _ => format!(
+                "/cm?cmnd=Power{}%20{}",
+                u16::from(self.relay) + 1,
+                if on { "On" } else { "Off" }
+            ),
+        }
+    }
+
+    fn switch(&self, on: bool) -> Result<String, String> {
+        self.request(&self.get_path(on))
+    }
+
+    fn request(&self, path: &str) -> Result<String, String> {
+        let (status, response) =
+            http_request(&self.address, "GET", path, None, None, self.timeout)?;
+        if status == 200 {
+            Ok(response)
+        } else {
+            Err(format!(
+                "relay {} returned {}: {}",
+                self.address,
+                status,
+                response.trim()
+            ))
+        }
+    }
+}
+
+impl PowerBackend for RelayBackend {
+    fn run(&self, action: &Type) -> PowerResult {
+        PowerResult::measure(action.clone(), &self.address, || match action {
+            Type::ON => self.switch(true),
+            Type::OFF => self.switch(false),
+            Type::REBOOT => {
+                self.switch(false)?;
+                thread::sleep(self.delay);
+                self.switch(true)
+            }
+            Type::STATUS => self.get_state().map(|s| s.to_string()),
+        })
+    }
+
+    /// Reads `ison` from Shelly or the `POWER` entry of the relay from Tasmota.
+    fn get_state(&self) -> Result<PowerState, String> {
+        let response = self.request(&self.get_status_path())?;
+        let status: serde_json::Value = serde_json::from_str(&response)
+            .map_err(|e| format!("relay {} sent invalid JSON: {}", self.address, e))?;
+        let value = match self.backend {
+            Backend::Shelly => status["ison"].clone(),
+            _ => status
+                .get(format!("POWER{}", u16::from(self.relay) + 1))
+                .or_else(|| status.get("POWER"))
+                .cloned()
+                .unwrap_or_default(),
+        };
+        Ok(match value {
+            serde_json::Value::Bool(true) => PowerState::On,
+            serde_json::Value::Bool(false) => PowerState::Off,
+            serde_json::Value::String(state) => PowerState::parse(&state),
+            _ => PowerState::Unknown,
+        })
+    }
+
+    fn get_name(&self) -> &'static str {
+        self.backend.get_name()
+    }
+}
+
+/// Switches the power of a USB hub port with uhubctl, for nodes powered over USB.
+#[derive(Debug, Clone)]
+pub struct UhubctlBackend {
+    pub command: String,
+    pub location: String,
+    pub port: String,
+    pub timeout: Duration,
+    pub runner: Option<Arc<dyn CommandRunner>>,
+}
+
+impl UhubctlBackend {
+    /// Returns the arguments for `action`; asking for the status omits `-a`.
+    #[must_use]
+    pub fn get_arguments(&self, action: &Type) -> Vec<String> {
+        let mut arguments = vec![
+            String::from("-l"),
+            self.location.clone(),
+            String::from("-p"),
+            self.port.clone(),
+        ];
+        let action = match action {
+            Type::ON => "on",
+            Type::OFF => "off",
+            Type::REBOOT => "cycle",
+            Type::STATUS => return arguments,
+        };
+        arguments.push(String::from("-a"));
+        arguments.push(String::from(action));
+        arguments
+    }
+}
+
+impl PowerBackend for UhubctlBackend {
+    fn run(&self, action: &Type) -> PowerResult {
+        let power_action =
+            PowerAction::new(action.clone(), &self.command, self.get_arguments(action));
+        match &self.runner {
+            Some(runner) => power_action.execute_with(&**runner, self.timeout),
+            None => power_action.execute(self.timeout),
+        }
+    }
+
+    /// Reads the port line, such as `Port 2: 0503 power highspeed enable connect`, of the
+    /// last state uhubctl printed.
+    fn get_state(&self) -> Result<PowerState, String> {
+        let output = self.run(&Type::STATUS).into_result()?;
+        let prefix = format!("Port {}:", self.port);
+        let line = output
+            .lines()
+            .map(str::trim)
+            .rfind(|l| l.starts_with(&prefix));
+        Ok(match line {
+            Some(line) if line.split_whitespace().any(|w| w == "off") => PowerState::Off,
+            Some(line) if line.split_whitespace().any(|w| w == "power") => PowerState::On,
+            _ => PowerState::Unknown,
+        })
+    }
+
+    fn get_name(&self) -> &'static str {
+        Backend::Uhubctl.get_name()
+    }
+}
+
+/// Wakes a node by sending a magic packet. It can only switch nodes on.
+#[derive(Debug, Clone)]
+pub struct WakeOnLanBackend {
+    pub mac_address: MacAddress,
+    pub broadcast: String,
+}
+
+impl WakeOnLanBackend {
+    /// Returns six bytes of `0xff` followed by the MAC address sixteen times.
+    #[must_use]
+    pub fn get_magic_packet(&self) -> Vec<u8> {
+        let mut packet = vec![0xff; 6];
+        for _ in 0..16 {
+            packet.extend_from_slice(&self.mac_address.get_octets());
+        }
+        packet
+    }
+}
+
+impl PowerBackend for WakeOnLanBackend {
+    fn run(&self, action: &Type) -> PowerResult {
+        PowerResult::measure(action.clone(), &self.broadcast, || match action {
+            Type::ON => {
+                let socket = UdpSocket::bind("0.0.0.0:0").map_err(|e| e.to_string())?;
+                socket.set_broadcast(true).map_err(|e| e.to_string())?;
+                socket
+                    .send_to(&self.get_magic_packet(), &self.broadcast)
+                    .map_err(|e| format!("{}: {}", self.broadcast, e))?;
+                Ok(String::new())
+            }
+            Type::STATUS => Ok(PowerState::Unknown.to_string()),
+            _ => Err(format!("wake-on-lan cannot {:?} a node", action)),
+        })
+    }
+
+    /// A magic packet gets no answer, so the state is never known.
+    fn get_state(&self) -> Result<PowerState, String> {
+        Ok(PowerState::Unknown)
+    }
+
+    fn get_name(&self) -> &'static str {
+        Backend::WakeOnLan.get_name()
+    }
+}
+
+/// Creates the backend `power` configures for `node`, recording its actions in the audit
+/// sink if one is set.
+///
+/// # Errors
+///
+/// Will return `Err` if a setting of `power` is invalid
+pub fn from_config(power: &PowerConfig, node: &Node) -> Result<Box<dyn PowerBackend>, String> {
+    let setting = |key: &str| power.get_setting(key).cloned().unwrap_or_default();
+    let timeout = power.get_duration("timeout")?.unwrap_or(DEFAULT_TIMEOUT);
+    let backend: Box<dyn PowerBackend> = match power.backend {
+        Backend::Command => Box::new(power.get_action_set()),
+        Backend::Redfish => Box::new(RedfishBackend {
+            address: setting("address"),
+            system: power
+                .get_setting("system")
+                .map_or_else(|| String::from(DEFAULT_REDFISH_SYSTEM), String::clone),
+            username: setting("username"),
+            password: setting("password"),
+            timeout,
+        }),
+        Backend::Tasmota | Backend::Shelly => Box::new(RelayBackend {
+            backend: power.backend,
+            address: setting("address"),
+            relay: match power.get_setting("relay") {
+                Some(relay) => parse_relay(relay)?,
+                None => 0,
+            },
+            delay: power
+                .get_duration("delay")?
+                .unwrap_or_else(|| Duration::from_secs(5)),
+            timeout,
+        }),
+        Backend::Uhubctl => Box::new(UhubctlBackend {
+            command: power
+                .get_setting("command")
+                .map_or_else(|| String::from("uhubctl"), String::clone),
+            location: setting("location"),
+            port: setting("port"),
+            timeout: power
+                .get_duration("timeout")?
+                .unwrap_or(power_action::DEFAULT_TIMEOUT),
+            runner: power.get_runner()?,
+        }),
+        Backend::WakeOnLan => Box::new(WakeOnLanBackend {
+            mac_address: match power.get_setting("mac-address") {
+                Some(mac_address) => MacAddress::parse(mac_address)?,
+                None => node.mac_address,
+            },
+            broadcast: power
+                .get_setting("broadcast")
+                .map_or_else(|| String::from(DEFAULT_BROADCAST), String::clone),
+        }),
+    };
+    Ok(power_audit::audit(backend, &node.id))
+}
+
+/// Parses the relay of a smart plug, counted from 0.
+///
+/// # Errors
+///
+/// Will return `Err` if `value` is not a number or out of the range of relays
+pub fn parse_relay(value: &str) -> Result<u8, String> {
+    value
+        .parse::<u8>()
+        .ok()
+        .filter(|r| *r < MAX_RELAYS)
+        .ok_or_else(|| {
+            format!(
+                "relay {} is not a number from 0 to {}",
+                value,
+                MAX_RELAYS - 1
+            )
+        })
+}
+
+/// Sends a minimal HTTP/1.0 request and returns the status code and body of the response.
+fn http_request(
+    address: &str,
+    method: &str,
+    path: &str,
+    auth: Option<&str>,
+    body: Option<&str>,
+    timeout: Duration,
+) -> Result<(u16, String), String> {
+    check_address(address)?;
+    let host = address.trim_start_matches("http://").trim_end_matches('/');
+    let socket_address = if host.contains(':') {
+        host.to_socket_addrs()
+    } else {
+        (host, 80).to_socket_addrs()
+    }
+    .map_err(|e| format!("{}: {}", host, e))?
+    .next()
+    .ok_or_else(|| format!("{} could not be resolved", host))?;
+    let mut stream = connect(&socket_address, timeout)?;
+    let mut headers = vec![
+        format!("{} {} HTTP/1.0", method, path),
+        format!("Host: {}", host),
+        String::from("Accept: application/json"),
+    ];
+    if let Some(auth) = auth {
+        headers.push(format!("Authorization: Basic {}", base64(auth.as_bytes())));
+    }
+    let body = body.unwrap_or_default();
+    if !body.is_empty() {
+        headers.push(String::from("Content-Type: application/json"));
+        headers.push(format!("Content-Length: {}", body.len()));
+    }
+    let request = format!("{}\r\n\r\n{}", headers.join("\r\n"), body);
+    stream
+        .write_all(request.as_bytes())
+        .map_err(|e| format!("{}: {}", host, e))?;
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .map_err(|e| format!("{}: {}", host, e))?;
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse::<u16>().ok())
+        .ok_or_else(|| format!("{} sent an invalid response", host))?;
+    Ok((status, body.to_string()))
+}
+
+/// Checks that `address` is a host, optionally with a port, or an `http://` URL of one.
+///
+/// # Errors
+///
+/// Will return `Err` if `address` asks for HTTPS or another scheme
+pub fn check_address(address: &str) -> Result<(), String> {
+    if address.starts_with("https://") {
+        return Err(format!(
+            "{} uses HTTPS, which is not supported; use a TLS terminating proxy and its \
+             http:// address",
+            address
+        ));
+    }
+    if address.trim_start_matches("http://").contains("://") {
+        return Err(format!("{} is not an http:// address", address));
+    }
+    Ok(())
+}
+
+fn connect(address: &SocketAddr, timeout: Duration) -> Result<TcpStream, String> {
+    let stream =
+        TcpStream::connect_timeout(address, timeout).map_err(|e| format!("{}: {}", address, e))?;
+    stream
+        .set_read_timeout(Some(timeout))
+        .and_then(|()| stream.set_write_timeout(Some(timeout)))
+        .map_err(|e| format!("{}: {}", address, e))?;
+    Ok(stream)
+}
+
+fn base64(input: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut output = String::new();
+    for chunk in input.chunks(3) {
+        let bytes = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let triple = u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(char::from(
+                    ALPHABET[(triple >> (18 - 6 * i) & 0x3f) as usize],
+                ));
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::thread::JoinHandle;
+
+    use super::*;
+    use crate::command_runner::{CommandOutput, RecordingRunner};
+
+    /// Answers one request with `response` and returns the address to send it to and the
+    /// request received.
+    fn serve(response: &'static str) -> (String, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0; 1024];
+            loop {
+                let size = stream.read(&mut buffer).unwrap();
+                request.extend_from_slice(&buffer[..size]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                    let length = head
+                        .lines()
+                        .find_map(|l| l.strip_prefix("Content-Length: "))
+                        .map_or(0, |l| l.parse::<usize>().unwrap());
+                    if body.len() >= length {
+                        break;
+                    }
+                }
+            }
+            stream.write_all(response.as_bytes()).unwrap();
+            String::from_utf8(request).unwrap()
+        });
+        (address, handle)
+    }
+
+    fn relay(backend: Backend, address: &str, relay: u8) -> RelayBackend {
+        RelayBackend {
+            backend,
+            address: address.to_string(),
+            relay,
+            delay: Duration::from_millis(1),
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    #[test]
+    fn base64_pads_to_whole_quads() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64(b"admin:secret"), "YWRtaW46c2VjcmV0");
+    }
+
+    #[test]
+    fn http_request_sends_auth_and_body() {
+        let (address, request) = serve("HTTP/1.0 201 Created\r\nServer: stub\r\n\r\n{\"a\":1}");
+        let (status, body) = http_request(
+            &format!("http://{}/", address),
+            "POST",
+            "/path",
+            Some("admin:secret"),
+            Some("{}"),
+            Duration::from_secs(5),
+        )
+        .unwrap();
+        assert_eq!(status, 201);
+        assert_eq!(body, "{\"a\":1}");
+        let request = request.join().unwrap();
+        assert!(request.starts_with("POST /path HTTP/1.0\r\n"));
+        assert!(request.contains("Authorization: Basic YWRtaW46c2VjcmV0\r\n"));
+        assert!(request.contains("Content-Length: 2\r\n"));
+        assert!(request.ends_with("\r\n\r\n{}"));
+    }
+
+    #[test]
+    fn http_request_rejects_invalid_responses() {
+        let (address, _) = serve("garbage");
+        assert!(http_request(&address, "GET", "/", None, None, Duration::from_secs(5)).is_err());
+    }
+
+    #[test]
+    fn redfish_resets_and_reads_state() {
+        let (address, request) = serve("HTTP/1.1 204 No Content\r\n\r\n");
+        let backend = RedfishBackend {
+            address,
+            system: String::from(DEFAULT_REDFISH_SYSTEM),
+            username: String::from("admin"),
+            password: String::from("secret"),
+            timeout: Duration::from_secs(5),
+        };
+        assert!(backend.run(&Type::REBOOT).is_success());
+        let request = request.join().unwrap();
+        assert!(
+            request.starts_with("POST /redfish/v1/Systems/1/Actions/ComputerSystem.Reset HTTP/1.0")
+        );
+        assert!(request.ends_with("{\"ResetType\":\"ForceRestart\"}"));
+
+        let (address, _) = serve("HTTP/1.1 200 OK\r\n\r\n{\"PowerState\":\"Off\"}");
+        let backend = RedfishBackend { address, ..backend };
+        assert_eq!(backend.get_state(), Ok(PowerState::Off));
+
+        let (address, _) = serve("HTTP/1.1 401 Unauthorized\r\n\r\n");
+        let backend = RedfishBackend { address, ..backend };
+        assert!(!backend.run(&Type::ON).is_success());
+    }
+
+    #[test]
+    fn tasmota_reads_the_state_of_its_relay() {
+        let (address, request) = serve("HTTP/1.1 200 OK\r\n\r\n{\"POWER2\":\"ON\"}");
+        assert_eq!(
+            relay(Backend::Tasmota, &address, 1).get_state(),
+            Ok(PowerState::On)
+        );
+        assert!(request
+            .join()
+            .unwrap()
+            .starts_with("GET /cm?cmnd=Power2 HTTP/1.0"));
+
+        let (address, _) = serve("HTTP/1.1 200 OK\r\n\r\n{\"POWER\":\"OFF\"}");
+        assert_eq!(
+            relay(Backend::Tasmota, &address, 0).get_state(),
+            Ok(PowerState::Off)
+        );
+
+        let (address, request) = serve("HTTP/1.1 200 OK\r\n\r\n{\"POWER32\":\"OFF\"}");
+        assert!(relay(Backend::Tasmota, &address, 31)
+            .run(&Type::OFF)
+            .is_success());
+        assert!(request
+            .join()
+            .unwrap()
+            .starts_with("GET /cm?cmnd=Power32%20Off HTTP/1.0"));
+    }
+
+    #[test]
+    fn shelly_reads_ison() {
+        let (address, request) = serve("HTTP/1.1 200 OK\r\n\r\n{\"ison\":false}");
+        assert_eq!(
+            relay(Backend::Shelly, &address, 0).get_state(),
+            Ok(PowerState::Off)
+        );
+        assert!(request.join().unwrap().starts_with("GET /relay/0 HTTP/1.0"));
+
+        let (address, _) = serve("HTTP/1.1 200 OK\r\n\r\nnot json");
+        assert!(relay(Backend::Shelly, &address, 0).get_state().is_err());
+    }
+
+    #[test]
+    fn relays_out_of_range_are_rejected() {
+        assert_eq!(parse_relay("31"), Ok(31));
+        assert!(parse_relay("32").is_err());
+        assert!(parse_relay("255").is_err());
+        assert!(parse_relay("-1").is_err());
+    }
+
+    #[test]
+    fn https_addresses_are_rejected() {
+        let error = http_request(
+            "https://bmc.example.org/",
+            "GET",
+            "/redfish/v1",
+            None,
+            None,
+            Duration::from_secs(1),
+        )
+        .unwrap_err();
+        assert!(error.contains("TLS terminating proxy"));
+        assert!(check_address("ftp://bmc").is_err());
+        assert!(check_address("http://bmc:8080").is_ok());
+        assert!(check_address("10.0.0.2").is_ok());
+    }
+
+    #[test]
+    fn uhubctl_runs_with_the_runner_and_reads_the_port() {
+        let runner = Arc::new(RecordingRunner::new().with_output(
+            "uhubctl",
+            CommandOutput::new(
+                0,
+                "Current status for hub 1-1 [2109:3431 USB2.0 Hub, USB 2.10, 4 ports, ppps]\n  \
+                 Port 2: 0503 power highspeed enable connect [0424:ec00]\n  \
+                 Port 3: 0000 off\n",
+            ),
+        ));
+        let backend = |port: &str| UhubctlBackend {
+            command: String::from("uhubctl"),
+            location: String::from("1-1"),
+            port: String::from(port),
+            timeout: Duration::from_secs(5),
+            runner: Some(Arc::clone(&runner) as Arc<dyn CommandRunner>),
+        };
+        assert!(backend("2").run(&Type::REBOOT).is_success());
+        assert_eq!(backend("2").get_state(), Ok(PowerState::On));
+        assert_eq!(backend("3").get_state(), Ok(PowerState::Off));
+        assert_eq!(backend("4").get_state(), Ok(PowerState::Unknown));
+        assert_eq!(
+            runner.get_calls()[..2],
+            [
+                vec!["uhubctl", "-l", "1-1", "-p", "2", "-a", "cycle"],
+                vec!["uhubctl", "-l", "1-1", "-p", "2"],
+            ]
+        );
+    }
+
+    #[test]
+    fn wake_on_lan_sends_the_magic_packet() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let backend = WakeOnLanBackend {
+            mac_address: MacAddress::parse("b8:27:eb:01:02:03").unwrap(),
+            broadcast: socket.local_addr().unwrap().to_string(),
+        };
+        assert!(backend.run(&Type::ON).is_success());
+        assert!(!backend.run(&Type::OFF).is_success());
+        let mut packet = [0; 256];
+        let size = socket.recv(&mut packet).unwrap();
+        assert_eq!(size, 102);
+        assert_eq!(packet[..6], [0xff; 6]);
+        for copy in packet[6..size].chunks(6) {
+            assert_eq!(copy, [0xb8, 0x27, 0xeb, 0x01, 0x02, 0x03]);
+        }
+    }
+}
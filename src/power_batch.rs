@@ -0,0 +1,264 @@
+use std::fmt;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::node::Node;
+use crate::power_action::{PowerResult, Type};
+use crate::power_backend::PowerBackend;
+use crate::retry_policy::RetryPolicy;
+
+/// How a batch of power actions is paced. At most `concurrency` actions run at once and
+/// successive actions start at least `delay` apart, so that a PDU is not overloaded.
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    pub concurrency: usize,
+    pub delay: Duration,
+    /// Reboot by switching off, waiting `reboot_wait` and switching on instead of using the
+    /// reboot action of the backend.
+    pub split_reboot: bool,
+    pub reboot_wait: Duration,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        BatchOptions {
+            concurrency: 4,
+            delay: Duration::from_secs(1),
+            split_reboot: false,
+            reboot_wait: Duration::from_secs(5),
+        }
+    }
+}
+
+/// A node to switch in a batch, with the backend and retry policy to use.
+#[derive(Debug)]
+pub struct BatchTarget {
+    pub node: String,
+    pub backend: Result<Box<dyn PowerBackend>, String>,
+    pub retry: RetryPolicy,
+}
+
+impl BatchTarget {
+    /// Uses the power configuration of `node`; a missing or invalid one fails the node once
+    /// the batch runs.
+    #[must_use]
+    pub fn from_node(node: &Node) -> Self {
+        let retry = node
+            .power
+            .as_ref()
+            .and_then(|p| p.get_retry_policy().ok())
+            .unwrap_or_default();
+        BatchTarget {
+            node: node.id.clone(),
+            backend: node.get_power_backend(),
+            retry,
+        }
+    }
+}
+
+/// The results of the actions performed on one node, more than one if a reboot was split.
+#[derive(Debug, Clone)]
+pub struct BatchResult {
+    pub node: String,
+    pub backend: String,
+    pub results: Vec<PowerResult>,
+}
+
+impl BatchResult {
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        !self.results.is_empty() && self.results.iter().all(PowerResult::is_success)
+    }
+
+    #[must_use]
+    pub fn get_duration(&self) -> Duration {
+        self.results.iter().map(|r| r.duration).sum()
+    }
+}
+
+impl fmt::Display for BatchResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "node {}:", self.node)?;
+        for result in &self.results {
+            write!(f, " {}", result)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchReport {
+    pub action: Type,
+    pub results: Vec<BatchResult>,
+    pub duration: Duration,
+}
+
+impl BatchReport {
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        self.results.iter().all(BatchResult::is_success)
+    }
+
+    #[must_use]
+    pub fn get_succeeded(&self) -> Vec<String> {
+        self.results
+            .iter()
+            .filter(|r| r.is_success())
+            .map(|r| r.node.clone())
+            .collect()
+    }
+
+    #[must_use]
+    pub fn get_failed(&self) -> Vec<&BatchResult> {
+        self.results.iter().filter(|r| !r.is_success()).collect()
+    }
+}
+
+impl fmt::Display for BatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:?} succeeded on {} of {} node(s) in {} ms",
+            self.action,
+            self.get_succeeded().len(),
+            self.results.len(),
+            self.duration.as_millis()
+        )?;
+        for result in self.get_failed() {
+            writeln!(f, "  {}", result)?;
+        }
+        Ok(())
+    }
+}
+
+/// Performs `action` on every node of `nodes` with its configured backend.
+#[must_use]
+pub fn run_nodes(nodes: &[Node], action: &Type, options: &BatchOptions) -> BatchReport {
+    run(
+        nodes.iter().map(BatchTarget::from_node).collect(),
+        action,
+        options,
+    )
+}
+
+/// Performs `action` on every target, paced as `options` demands, and returns the results in
+/// the order of `targets`.
+/// Targets left without a result by a panicking worker are reported as failed.
+#[must_use]
+pub fn run(targets: Vec<BatchTarget>, action: &Type, options: &BatchOptions) -> BatchReport {
+    let started = Instant::now();
+    let count = targets.len();
+    let described = targets
+        .iter()
+        .map(|t| (t.node.clone(), get_backend_name(t)))
+        .collect::<Vec<(String, String)>>();
+    let queue = Arc::new(Mutex::new(
+        targets.into_iter().enumerate().collect::<Vec<_>>(),
+    ));
+    let next_start = Arc::new(Mutex::new(Instant::now()));
+    let (sender, receiver) = mpsc::channel();
+    let mut workers = Vec::new();
+    for _ in 0..options.concurrency.clamp(1, count.max(1)) {
+        let queue = Arc::clone(&queue);
+        let next_start = Arc::clone(&next_start);
+        let sender = sender.clone();
+        let action = action.clone();
+        let options = options.clone();
+        workers.push(thread::spawn(move || loop {
+            let (index, target) = match queue.lock() {
+                Ok(mut queue) if !queue.is_empty() => queue.remove(0),
+                _ => break,
+            };
+            let results = perform(&target, &action, &options, &next_start);
+            if sender
+                .send((
+                    index,
+                    BatchResult {
+                        backend: get_backend_name(&target),
+                        node: target.node,
+                        results,
+                    },
+                ))
+                .is_err()
+            {
+                break;
+            }
+        }));
+    }
+    drop(sender);
+    let mut results: Vec<Option<BatchResult>> = (0..count).map(|_| None).collect();
+    for (index, result) in &receiver {
+        results[index] = Some(result);
+    }
+    let panicked = workers
+        .into_iter()
+        .map(thread::JoinHandle::join)
+        .filter(Result::is_err)
+        .count();
+    BatchReport {
+        action: action.clone(),
+        results: results
+            .into_iter()
+            .zip(described)
+            .map(|(result, (node, backend))| {
+                result.unwrap_or_else(|| {
+                    let mut result = PowerResult::new(action.clone(), &node);
+                    result.error = Some(format!("no result, {} of the workers panicked", panicked));
+                    BatchResult {
+                        node,
+                        backend,
+                        results: vec![result],
+                    }
+                })
+            })
+            .collect(),
+        duration: started.elapsed(),
+    }
+}
+
+fn get_backend_name(target: &BatchTarget) -> String {
+    target
+        .backend
+        .as_ref()
+        .map_or_else(|_| String::from("none"), |b| b.get_name().to_string())
+}
+
+fn perform(
+    target: &BatchTarget,
+    action: &Type,
+    options: &BatchOptions,
+    next_start: &Mutex<Instant>,
+) -> Vec<PowerResult> {
+    let backend = match &target.backend {
+        Ok(backend) => backend,
+        Err(e) => {
+            let mut result = PowerResult::new(action.clone(), &target.node);
+            result.error = Some(e.clone());
+            return vec![result];
+        }
+    };
+    wait_for_turn(next_start, options.delay);
+    if *action != Type::REBOOT || !options.split_reboot {
+        return vec![backend.run_with_retries(action, &target.retry)];
+    }
+    let off = backend.run_with_retries(&Type::OFF, &target.retry);
+    if !off.is_success() {
+        return vec![off];
+    }
+    thread::sleep(options.reboot_wait);
+    wait_for_turn(next_start, options.delay);
+    let on = backend.run_with_retries(&Type::ON, &target.retry);
+    vec![off, on]
+}
+
+/// Blocks until `delay` has passed since the previous action of the batch started.
+fn wait_for_turn(next_start: &Mutex<Instant>, delay: Duration) {
+    if let Ok(mut next_start) = next_start.lock() {
+        let now = Instant::now();
+        if *next_start > now {
+            thread::sleep(*next_start - now);
+        }
+        *next_start = Instant::now() + delay;
+    }
+}
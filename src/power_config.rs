@@ -0,0 +1,416 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use config::Value;
+use serde::{Deserialize, Serialize};
+
+use crate::command_runner;
+use crate::command_runner::CommandRunner;
+use crate::node;
+use crate::node::Node;
+use crate::power_action::{PowerAction, Type, DEFAULT_TIMEOUT};
+use crate::power_action_set::PowerActionSet;
+use crate::power_backend;
+use crate::retry_policy::RetryPolicy;
+use crate::template;
+
+pub const KEYS: [&str; 4] = ["on", "off", "reboot", "status"];
+/// Settings every backend accepts to retry failed actions.
+pub const RETRY_KEYS: [&str; 3] = ["retries", "retry-delay", "retry-backoff"];
+
+/// How the power of a node is switched.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Default)]
+pub enum Backend {
+    #[default]
+    Command,
+    Redfish,
+    Tasmota,
+    Shelly,
+    Uhubctl,
+    WakeOnLan,
+}
+
+impl Backend {
+    #[must_use]
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Backend::Command => "command",
+            Backend::Redfish => "redfish",
+            Backend::Tasmota => "tasmota",
+            Backend::Shelly => "shelly",
+            Backend::Uhubctl => "uhubctl",
+            Backend::WakeOnLan => "wol",
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if `line` could not be parsed
+    pub fn parse(line: &str) -> Result<Backend, String> {
+        match line.to_lowercase().as_str() {
+            "command" => Ok(Backend::Command),
+            "redfish" => Ok(Backend::Redfish),
+            "tasmota" => Ok(Backend::Tasmota),
+            "shelly" => Ok(Backend::Shelly),
+            "uhubctl" => Ok(Backend::Uhubctl),
+            "wol" | "wake-on-lan" => Ok(Backend::WakeOnLan),
+            _ => Err(format!("{} is not a power backend", line)),
+        }
+    }
+
+    /// Returns the settings the backend requires and those it accepts besides.
+    #[must_use]
+    pub fn get_keys(&self) -> (&'static [&'static str], &'static [&'static str]) {
+        match self {
+            Backend::Command => (&[], &["timeout", "runner"]),
+            Backend::Redfish => (&["address", "username", "password"], &["system", "timeout"]),
+            Backend::Tasmota | Backend::Shelly => (&["address"], &["relay", "delay", "timeout"]),
+            Backend::Uhubctl => (&["location", "port"], &["command", "timeout", "runner"]),
+            Backend::WakeOnLan => (&[], &["broadcast", "mac-address"]),
+        }
+    }
+}
+
+/// The power backend of a node and its settings, as configured in the inventory. The
+/// command backend runs the `on`, `off`, `reboot` and `status` command lines.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, Eq, PartialEq)]
+pub struct PowerConfig {
+    #[serde(default)]
+    pub backend: Backend,
+    pub on: Option<String>,
+    pub off: Option<String>,
+    pub reboot: Option<String>,
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub settings: BTreeMap<String, String>,
+    /// Whether `backend` was configured rather than left to the default or a group.
+    #[serde(default)]
+    backend_set: bool,
+}
+
+impl PowerConfig {
+    /// Accepts a table with an optional `backend` and its settings, or the commands `on`,
+    /// `off`, `reboot` and `status` for the command backend. Commands and settings may hold
+    /// `{field}` placeholders that are filled per node, see [`PowerConfig::render`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `value` is not such a table, a command could not be parsed or a
+    /// setting is missing or unknown
+    pub fn from_config(value: &Value) -> Result<Self, String> {
+        let power = PowerConfig::from_partial_config(value)?;
+        power.check()?;
+        Ok(power)
+    }
+
+    /// Reads the table like [`PowerConfig::from_config`], but leaves checking the settings
+    /// to [`PowerConfig::check`], as the rest may be inherited from a group.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `value` is not a table of strings or a placeholder is malformed
+    pub fn from_partial_config(value: &Value) -> Result<Self, String> {
+        let table = value.clone().into_table().map_err(|e| e.to_string())?;
+        let mut power = PowerConfig::default();
+        for (key, value) in table {
+            let value = value.into_str().map_err(|e| e.to_string())?;
+            if key == "backend" {
+                power.backend = Backend::parse(&value)?;
+                power.backend_set = true;
+                continue;
+            }
+            template::get_placeholders(&value).map_err(|e| format!("{} {}", key, e))?;
+            match Type::parse(&key) {
+                Ok(Type::ON) => power.on = Some(value),
+                Ok(Type::OFF) => power.off = Some(value),
+                Ok(Type::REBOOT) => power.reboot = Some(value),
+                Ok(Type::STATUS) => power.status = Some(value),
+                Err(_) => {
+                    power.settings.insert(key, value);
+                }
+            }
+        }
+        Ok(power)
+    }
+
+    /// Checks that the commands parse and that the backend has all the settings it requires
+    /// and none it does not know.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` with the first problem found
+    pub fn check(&self) -> Result<(), String> {
+        let (required, optional) = self.backend.get_keys();
+        for key in self.settings.keys() {
+            if !required.contains(&key.as_str())
+                && !optional.contains(&key.as_str())
+                && !RETRY_KEYS.contains(&key.as_str())
+            {
+                return Err(format!(
+                    "{} is not a setting of the {} backend",
+                    key,
+                    self.backend.get_name()
+                ));
+            }
+        }
+        for action in &[Type::ON, Type::OFF, Type::REBOOT, Type::STATUS] {
+            if let Some(command) = self.get(action) {
+                if self.backend != Backend::Command {
+                    return Err(format!(
+                        "{} is not a setting of the {} backend",
+                        action.get_name(),
+                        self.backend.get_name()
+                    ));
+                }
+                PowerAction::parse(action.clone(), command)?;
+            }
+        }
+        for key in required {
+            if !self.settings.contains_key(*key) {
+                return Err(format!(
+                    "{} backend is missing {}",
+                    self.backend.get_name(),
+                    key
+                ));
+            }
+        }
+        self.get_duration("timeout")?;
+        self.get_retry_policy()?;
+        if let (Backend::Redfish | Backend::Tasmota | Backend::Shelly, Some(address)) =
+            (self.backend, self.get_setting("address"))
+        {
+            power_backend::check_address(address)?;
+        }
+        if let Some(relay) = self.get_setting("relay").filter(|r| !r.contains('{')) {
+            power_backend::parse_relay(relay)?;
+        }
+        if self.get_setting("runner").is_some_and(|r| !r.contains('{')) {
+            self.get_runner()?;
+        }
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn get(&self, action: &Type) -> Option<&String> {
+        match action {
+            Type::ON => self.on.as_ref(),
+            Type::OFF => self.off.as_ref(),
+            Type::REBOOT => self.reboot.as_ref(),
+            Type::STATUS => self.status.as_ref(),
+        }
+    }
+
+    #[must_use]
+    pub fn get_setting(&self, key: &str) -> Option<&String> {
+        self.settings.get(key)
+    }
+
+    /// Returns a setting given in seconds.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the setting is not a number of seconds
+    pub fn get_duration(&self, key: &str) -> Result<Option<Duration>, String> {
+        match self.get_setting(key) {
+            Some(value) => value
+                .parse::<f64>()
+                .ok()
+                .filter(|s| s.is_finite() && *s >= 0.0)
+                .map(|s| Some(Duration::from_secs_f64(s)))
+                .ok_or_else(|| format!("{} {} is not a number of seconds", key, value)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the runner set with `runner`, such as `ssh://pi@power-host`, to run the
+    /// commands with instead of the global one.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the runner could not be parsed
+    pub fn get_runner(&self) -> Result<Option<Arc<dyn CommandRunner>>, String> {
+        self.get_setting("runner")
+            .map(|r| command_runner::parse(r))
+            .transpose()
+    }
+
+    /// Returns how failed actions are retried; without `retries` they are not.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if a retry setting is invalid
+    pub fn get_retry_policy(&self) -> Result<RetryPolicy, String> {
+        let mut policy = RetryPolicy::default();
+        if let Some(retries) = self.get_setting("retries") {
+            let retries = retries
+                .parse::<u32>()
+                .map_err(|_| format!("retries {} is not a number", retries))?;
+            policy.attempts = retries.saturating_add(1);
+        }
+        if let Some(delay) = self.get_duration("retry-delay")? {
+            policy.delay = delay;
+        }
+        if let Some(backoff) = self.get_setting("retry-backoff") {
+            policy.backoff = backoff
+                .parse::<f64>()
+                .ok()
+                .filter(|b| b.is_finite() && *b >= 1.0)
+                .ok_or_else(|| format!("retry-backoff {} is not a factor of 1 or more", backoff))?;
+        }
+        Ok(policy)
+    }
+
+    /// Fills the placeholders of every command and setting with the fields and labels of
+    /// `node`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if a placeholder is neither a field nor a label of `node`
+    pub fn render(&self, node: &Node) -> Result<PowerConfig, String> {
+        let render = |value: &String| template::render(value, |key| node.get_field(key));
+        let mut power = self.clone();
+        for command in &mut [
+            &mut power.on,
+            &mut power.off,
+            &mut power.reboot,
+            &mut power.status,
+        ] {
+            if let Some(value) = command.as_ref() {
+                **command = Some(render(value)?);
+            }
+        }
+        for value in power.settings.values_mut() {
+            *value = render(value)?;
+        }
+        Ok(power)
+    }
+
+    /// Checks that every placeholder is a node field or a label `is_label` accepts.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` with the first key that has an unknown placeholder
+    pub fn check_placeholders<F>(&self, is_label: F) -> Result<(), String>
+    where
+        F: Fn(&str) -> bool,
+    {
+        for (key, value) in self.get_values() {
+            for placeholder in template::get_placeholders(&value)? {
+                if !node::is_field(&placeholder) && !is_label(&placeholder) {
+                    return Err(format!(
+                        "{} {{{}}} is not a node field or label",
+                        key, placeholder
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the configured keys and values, as written to the inventory.
+    #[must_use]
+    pub fn get_values(&self) -> Vec<(String, String)> {
+        let mut values = Vec::new();
+        if self.backend_set || self.backend != Backend::Command {
+            values.push((String::from("backend"), self.backend.get_name().to_string()));
+        }
+        for (key, value) in KEYS
+            .iter()
+            .zip(&[&self.on, &self.off, &self.reboot, &self.status])
+        {
+            if let Some(value) = value {
+                values.push((key.to_string(), value.clone()));
+            }
+        }
+        for (key, value) in &self.settings {
+            values.push((key.clone(), value.clone()));
+        }
+        values
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.get_values().is_empty()
+    }
+
+    /// Fills every key missing here from `other`, the backend included unless it is set here.
+    /// Nothing is taken from a different backend. Returns the keys that were filled.
+    pub fn merge(&mut self, other: &PowerConfig) -> Vec<String> {
+        let mut filled = Vec::new();
+        if !self.backend_set && other.backend_set {
+            self.backend = other.backend;
+            self.backend_set = true;
+            filled.push(String::from("backend"));
+        }
+        if self.backend != other.backend {
+            return filled;
+        }
+        for (key, command, other) in &mut [
+            ("on", &mut self.on, &other.on),
+            ("off", &mut self.off, &other.off),
+            ("reboot", &mut self.reboot, &other.reboot),
+            ("status", &mut self.status, &other.status),
+        ] {
+            if command.is_none() && other.is_some() {
+                command.clone_from(other);
+                filled.push(key.to_string());
+            }
+        }
+        for (key, value) in &other.settings {
+            if !self.settings.contains_key(key) {
+                self.settings.insert(key.clone(), value.clone());
+                filled.push(key.clone());
+            }
+        }
+        filled
+    }
+
+    /// Removes a key as returned by [`PowerConfig::get_values`].
+    pub fn remove(&mut self, key: &str) {
+        match Type::parse(key) {
+            Ok(Type::ON) => self.on = None,
+            Ok(Type::OFF) => self.off = None,
+            Ok(Type::REBOOT) => self.reboot = None,
+            Ok(Type::STATUS) => self.status = None,
+            Err(_) if key == "backend" => {
+                self.backend = Backend::default();
+                self.backend_set = false;
+            }
+            Err(_) => {
+                self.settings.remove(key);
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn get_action_set(&self) -> PowerActionSet {
+        let parse = |action: Type| match self.get(&action) {
+            Some(command) => PowerAction::parse(action, command),
+            None => Err(format!("no {:?} power command is configured", action)),
+        };
+        PowerActionSet::new(parse(Type::ON), parse(Type::OFF), parse(Type::REBOOT))
+            .with_status(parse(Type::STATUS))
+            .with_runner(self.get_runner().ok().flatten())
+            .with_timeout(
+                self.get_duration("timeout")
+                    .ok()
+                    .flatten()
+                    .unwrap_or(DEFAULT_TIMEOUT),
+            )
+    }
+}
+
+impl fmt::Display for PowerConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let values = self
+            .get_values()
+            .into_iter()
+            .filter(|(key, _)| key != "password")
+            .map(|(key, value)| format!("{}: {}", key, value))
+            .collect::<Vec<String>>();
+        write!(f, "{}", values.join(", "))
+    }
+}
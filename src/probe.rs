@@ -0,0 +1,244 @@
+use std::collections::VecDeque;
+use std::io::Read;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream};
+use std::str;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::command_runner;
+use crate::node::Node;
+use crate::node_row::NodeRow;
+
+const SSH_PORT: u16 = 22;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Reachability {
+    Icmp,
+    Tcp(u16),
+}
+
+#[derive(Debug, Clone)]
+pub struct ProbeOptions {
+    pub reachability: Vec<Reachability>,
+    pub ssh_port: u16,
+    pub timeout: Duration,
+    pub concurrency: usize,
+    pub resolve_hostname: bool,
+    pub ssh_hostname: bool,
+    /// The `StrictHostKeyChecking` option of `ssh` when asking a node for its hostname, for
+    /// example `yes` to only accept known keys.
+    pub strict_host_key_checking: String,
+}
+
+impl Default for ProbeOptions {
+    fn default() -> Self {
+        ProbeOptions {
+            reachability: vec![Reachability::Icmp, Reachability::Tcp(SSH_PORT)],
+            ssh_port: SSH_PORT,
+            timeout: Duration::from_secs(2),
+            concurrency: 32,
+            resolve_hostname: true,
+            ssh_hostname: false,
+            strict_host_key_checking: String::from("accept-new"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProbeResult {
+    pub node: Node,
+    pub reachable: bool,
+    pub ssh_banner: Option<String>,
+    pub hostname: Option<String>,
+    pub duration: Duration,
+}
+
+impl ProbeResult {
+    /// A node is considered usable if it is reachable and accepts SSH connections.
+    #[must_use]
+    pub fn is_usable(&self) -> bool {
+        self.reachable && self.ssh_banner.is_some()
+    }
+
+    #[must_use]
+    pub fn into_row(self) -> NodeRow {
+        let usable = self.is_usable();
+        let ipv4_address = Some(self.node.ipv4_address.to_string());
+        NodeRow::new(
+            self.node,
+            Some(self.reachable),
+            self.hostname,
+            ipv4_address,
+            usable,
+        )
+    }
+}
+
+/// Probes a single node. Each check is bounded by the timeout of `options`.
+#[must_use]
+pub fn probe(node: &Node, options: &ProbeOptions) -> ProbeResult {
+    let started = Instant::now();
+    let address = node.ipv4_address;
+    let reachable = options.reachability.iter().any(|r| match r {
+        Reachability::Icmp => ping(address, options.timeout),
+        Reachability::Tcp(port) => connect(address, *port, options.timeout).is_some(),
+    });
+    let ssh_banner = if reachable {
+        get_ssh_banner(address, options.ssh_port, options.timeout)
+    } else {
+        None
+    };
+    let mut hostname = None;
+    if options.resolve_hostname {
+        hostname = reverse_lookup(address, options.timeout);
+    }
+    if hostname.is_none() && options.ssh_hostname && ssh_banner.is_some() {
+        hostname = get_ssh_hostname(
+            address,
+            options.ssh_port,
+            &options.strict_host_key_checking,
+            options.timeout,
+        );
+    }
+    ProbeResult {
+        node: node.clone(),
+        reachable,
+        ssh_banner,
+        hostname,
+        duration: started.elapsed(),
+    }
+}
+
+/// Probes `nodes` on up to `concurrency` threads and returns the results in the order of
+/// `nodes`. Each thread takes the next node as soon as it is done with its last one.
+#[must_use]
+pub fn probe_all(nodes: &[Node], options: &ProbeOptions) -> Vec<ProbeResult> {
+    let queue = Arc::new(Mutex::new(
+        nodes.iter().cloned().enumerate().collect::<VecDeque<_>>(),
+    ));
+    let (sender, receiver) = mpsc::channel();
+    let mut workers = Vec::new();
+    for _ in 0..options.concurrency.clamp(1, nodes.len().max(1)) {
+        let queue = Arc::clone(&queue);
+        let sender = sender.clone();
+        let options = options.clone();
+        workers.push(thread::spawn(move || loop {
+            let (index, node) = match queue.lock().map(|mut q| q.pop_front()) {
+                Ok(Some(next)) => next,
+                _ => break,
+            };
+            if sender.send((index, probe(&node, &options))).is_err() {
+                break;
+            }
+        }));
+    }
+    drop(sender);
+    let mut results = nodes
+        .iter()
+        .map(|_| None)
+        .collect::<Vec<Option<ProbeResult>>>();
+    for (index, result) in &receiver {
+        results[index] = Some(result);
+    }
+    for worker in workers {
+        let _ = worker.join();
+    }
+    results
+        .into_iter()
+        .zip(nodes)
+        .map(|(result, node)| {
+            result.unwrap_or_else(|| ProbeResult {
+                node: node.clone(),
+                reachable: false,
+                ssh_banner: None,
+                hostname: None,
+                duration: Duration::default(),
+            })
+        })
+        .collect()
+}
+
+#[must_use]
+pub fn ping(address: Ipv4Addr, timeout: Duration) -> bool {
+    let seconds = timeout.as_secs().max(1).to_string();
+    match command_runner::run(
+        "ping",
+        &["-c", "1", "-W", &seconds, &address.to_string()],
+        Some(timeout + Duration::from_secs(1)),
+    ) {
+        Ok(output) => output.is_success(),
+        Err(_) => false,
+    }
+}
+
+#[must_use]
+pub fn connect(address: Ipv4Addr, port: u16, timeout: Duration) -> Option<TcpStream> {
+    TcpStream::connect_timeout(&SocketAddr::new(IpAddr::V4(address), port), timeout).ok()
+}
+
+/// Returns the identification string an SSH server sends after connecting, for example
+/// `SSH-2.0-OpenSSH_8.4p1 Raspbian-5`.
+#[must_use]
+pub fn get_ssh_banner(address: Ipv4Addr, port: u16, timeout: Duration) -> Option<String> {
+    let mut stream = connect(address, port, timeout)?;
+    stream.set_read_timeout(Some(timeout)).ok()?;
+    let mut buffer = [0; 256];
+    let mut received = Vec::new();
+    while !received.contains(&b'\n') && received.len() < buffer.len() {
+        match stream.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(size) => received.extend_from_slice(&buffer[..size]),
+        }
+    }
+    let banner = String::from_utf8_lossy(&received);
+    banner
+        .lines()
+        .find(|l| l.starts_with("SSH-"))
+        .map(|l| l.trim().to_string())
+}
+
+#[must_use]
+pub fn reverse_lookup(address: Ipv4Addr, timeout: Duration) -> Option<String> {
+    let output =
+        command_runner::run("getent", &["hosts", &address.to_string()], Some(timeout)).ok()?;
+    if !output.is_success() {
+        return None;
+    }
+    output.stdout.split_whitespace().nth(1).map(str::to_string)
+}
+
+/// Asks the node itself for its hostname; requires key based SSH access.
+#[must_use]
+pub fn get_ssh_hostname(
+    address: Ipv4Addr,
+    port: u16,
+    strict_host_key_checking: &str,
+    timeout: Duration,
+) -> Option<String> {
+    let output = command_runner::run(
+        "ssh",
+        &[
+            "-o",
+            "BatchMode=yes",
+            "-o",
+            &format!("StrictHostKeyChecking={}", strict_host_key_checking),
+            "-o",
+            &format!("ConnectTimeout={}", timeout.as_secs().max(1)),
+            "-p",
+            &port.to_string(),
+            &address.to_string(),
+            "hostname",
+        ],
+        Some(timeout + Duration::from_secs(1)),
+    )
+    .ok()?;
+    if !output.is_success() {
+        return None;
+    }
+    let hostname = output.stdout.trim().to_string();
+    if hostname.is_empty() {
+        return None;
+    }
+    Some(hostname)
+}
@@ -0,0 +1,240 @@
+use std::fmt;
+
+use chrono::{NaiveDateTime, Utc};
+use rusqlite::Row;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+pub struct Reservation {
+    pub id: Option<i64>,
+    pub owner: String,
+    pub nodes: Vec<String>,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub reason: String,
+}
+
+impl Reservation {
+    /// # Errors
+    ///
+    /// Will return `Err` if no node is given or the window ends before it starts
+    pub fn new(
+        owner: &str,
+        nodes: &[String],
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+        reason: &str,
+    ) -> Result<Self, String> {
+        if nodes.is_empty() {
+            return Err(String::from("a reservation needs at least one node"));
+        }
+        if end <= start {
+            return Err(format!("reservation ends at {} before it starts", end));
+        }
+        let mut nodes = nodes.to_vec();
+        nodes.sort();
+        nodes.dedup();
+        Ok(Reservation {
+            id: None,
+            owner: String::from(owner),
+            nodes,
+            start,
+            end,
+            reason: String::from(reason),
+        })
+    }
+
+    /// Expects the columns `id`, `owner`, `nodes`, `start`, `end` and `reason`, with the
+    /// nodes stored as a JSON list.
+    #[must_use]
+    pub fn from_row(row: &Row) -> Self {
+        let nodes: String = row.get(2).unwrap_or_default();
+        Reservation {
+            id: row.get(0).unwrap_or_default(),
+            owner: row.get(1).unwrap_or_default(),
+            nodes: serde_json::from_str(&nodes).unwrap_or_default(),
+            start: row.get(3).unwrap_or_else(|_| Utc::now().naive_utc()),
+            end: row.get(4).unwrap_or_else(|_| Utc::now().naive_utc()),
+            reason: row.get(5).unwrap_or_default(),
+        }
+    }
+
+    /// Returns the nodes as the JSON list stored in the `nodes` column.
+    #[must_use]
+    pub fn get_nodes_json(&self) -> String {
+        serde_json::to_string(&self.nodes).unwrap_or_default()
+    }
+
+    #[must_use]
+    pub fn is_active(&self, now: NaiveDateTime) -> bool {
+        self.start <= now && now < self.end
+    }
+
+    #[must_use]
+    pub fn is_expired(&self, now: NaiveDateTime) -> bool {
+        self.end <= now
+    }
+
+    #[must_use]
+    pub fn contains(&self, node: &str) -> bool {
+        self.nodes.iter().any(|n| n.eq(node))
+    }
+
+    #[must_use]
+    pub fn overlaps(&self, start: NaiveDateTime, end: NaiveDateTime) -> bool {
+        self.start < end && start < self.end
+    }
+}
+
+impl fmt::Display for Reservation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} reserved {} from {} until {}",
+            self.owner,
+            self.nodes.join(", "),
+            self.start.format("%Y-%m-%d %H:%M"),
+            self.end.format("%Y-%m-%d %H:%M")
+        )?;
+        if !self.reason.is_empty() {
+            write!(f, " ({})", self.reason)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ReservationConflict {
+    pub node: String,
+    pub reservation: Reservation,
+}
+
+impl fmt::Display for ReservationConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "node {} is taken: {}", self.node, self.reservation)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Reservations {
+    reservations: Vec<Reservation>,
+}
+
+impl Reservations {
+    #[must_use]
+    pub fn new(reservations: Vec<Reservation>) -> Self {
+        Reservations { reservations }
+    }
+
+    #[must_use]
+    pub fn get_reservations(&self) -> Vec<Reservation> {
+        self.reservations.clone()
+    }
+
+    /// Returns every reservation of another owner that holds one of `nodes` during the
+    /// window from `start` to `end`.
+    #[must_use]
+    pub fn get_conflicts(
+        &self,
+        owner: &str,
+        nodes: &[String],
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Vec<ReservationConflict> {
+        let mut conflicts = Vec::new();
+        for reservation in &self.reservations {
+            if reservation.owner.eq(owner) || !reservation.overlaps(start, end) {
+                continue;
+            }
+            for node in nodes.iter().filter(|n| reservation.contains(n)) {
+                conflicts.push(ReservationConflict {
+                    node: node.clone(),
+                    reservation: reservation.clone(),
+                });
+            }
+        }
+        conflicts
+    }
+
+    /// Releases expired reservations before adding `reservation`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` with every existing reservation `reservation` conflicts with
+    pub fn add(&mut self, reservation: Reservation) -> Result<(), Vec<ReservationConflict>> {
+        self.release_expired(Utc::now().naive_local());
+        let conflicts = self.get_conflicts(
+            &reservation.owner,
+            &reservation.nodes,
+            reservation.start,
+            reservation.end,
+        );
+        if !conflicts.is_empty() {
+            return Err(conflicts);
+        }
+        self.reservations.push(reservation);
+        Ok(())
+    }
+
+    /// Checks whether `owner` may use `nodes` right now.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` with every active reservation of another owner holding one of `nodes`
+    pub fn check_claim(
+        &self,
+        owner: &str,
+        nodes: &[String],
+        now: NaiveDateTime,
+    ) -> Result<(), Vec<ReservationConflict>> {
+        let mut conflicts = Vec::new();
+        for reservation in &self.reservations {
+            if reservation.owner.eq(owner) || !reservation.is_active(now) {
+                continue;
+            }
+            for node in nodes.iter().filter(|n| reservation.contains(n)) {
+                conflicts.push(ReservationConflict {
+                    node: node.clone(),
+                    reservation: reservation.clone(),
+                });
+            }
+        }
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(conflicts)
+        }
+    }
+
+    #[must_use]
+    pub fn get_active(&self, now: NaiveDateTime) -> Vec<Reservation> {
+        self.reservations
+            .iter()
+            .filter(|r| r.is_active(now))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the active reservation holding `node`, if any.
+    #[must_use]
+    pub fn get_holder(&self, node: &str, now: NaiveDateTime) -> Option<&Reservation> {
+        self.reservations
+            .iter()
+            .find(|r| r.is_active(now) && r.contains(node))
+    }
+
+    pub fn release(&mut self, id: i64) -> Option<Reservation> {
+        let index = self.reservations.iter().position(|r| r.id == Some(id))?;
+        Some(self.reservations.remove(index))
+    }
+
+    /// Removes and returns every reservation that has ended by `now`.
+    pub fn release_expired(&mut self, now: NaiveDateTime) -> Vec<Reservation> {
+        let (expired, remaining) = self
+            .reservations
+            .drain(..)
+            .partition(|r: &Reservation| r.is_expired(now));
+        self.reservations = remaining;
+        expired
+    }
+}
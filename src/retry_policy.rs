@@ -0,0 +1,78 @@
+use std::convert::TryFrom;
+use std::thread;
+use std::time::Duration;
+
+use crate::power_action::PowerResult;
+
+/// How often a failed power action is tried again and how long to wait in between. The wait
+/// grows by `backoff` after every attempt, up to `max_delay`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    pub attempts: u32,
+    pub delay: Duration,
+    pub backoff: f64,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    #[must_use]
+    pub fn new(attempts: u32, delay: Duration) -> Self {
+        RetryPolicy {
+            attempts: attempts.max(1),
+            delay,
+            ..RetryPolicy::default()
+        }
+    }
+
+    #[must_use]
+    pub fn with_backoff(mut self, backoff: f64, max_delay: Duration) -> Self {
+        self.backoff = backoff.max(1.0);
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Returns how long to wait after the failed attempt `attempt`, counting from one.
+    #[must_use]
+    pub fn get_delay(&self, attempt: u32) -> Duration {
+        let exponent = i32::try_from(attempt.saturating_sub(1)).unwrap_or(i32::MAX);
+        let delay = self.delay.as_secs_f64() * self.backoff.powi(exponent);
+        if delay.is_finite() && delay < self.max_delay.as_secs_f64() {
+            Duration::from_secs_f64(delay)
+        } else {
+            self.max_delay
+        }
+    }
+
+    /// Performs `perform` until it succeeds or the attempts are used up, and returns the last
+    /// result with the number of attempts made.
+    pub fn run<F>(&self, mut perform: F) -> PowerResult
+    where
+        F: FnMut() -> PowerResult,
+    {
+        let mut attempt = 1;
+        let mut started = None;
+        loop {
+            let mut result = perform();
+            result.attempts = attempt;
+            result.started = *started.get_or_insert(result.started);
+            if result.is_success() || attempt >= self.attempts {
+                return result;
+            }
+            thread::sleep(self.get_delay(attempt));
+            attempt += 1;
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    /// A single attempt; retries wait one second and twice as long every time, at most 30
+    /// seconds.
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: 1,
+            delay: Duration::from_secs(1),
+            backoff: 2.0,
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
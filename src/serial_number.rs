@@ -0,0 +1,73 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[serde(try_from = "String", into = "String")]
+pub struct SerialNumber(String);
+
+impl SerialNumber {
+    /// Hexadecimal serials are lower-cased and the 16 digit form reported by a Raspberry Pi
+    /// (`10000000a1b2c3d4`) is shortened to the 8 digits its bootloader requests files with.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `value` is empty, a placeholder or contains whitespace
+    pub fn parse(value: &str) -> Result<SerialNumber, String> {
+        let trimmed = value.trim();
+        let serial = trimmed
+            .strip_prefix("0x")
+            .or_else(|| trimmed.strip_prefix("0X"))
+            .unwrap_or(trimmed);
+        if serial.is_empty() || serial.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return Err(format!("{:?} is not a valid serial number", value));
+        }
+        if serial.chars().all(|c| c == '0' || c == '-') {
+            return Err(format!("{} is not a usable serial number", value));
+        }
+        if !serial.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Ok(SerialNumber(serial.to_string()));
+        }
+        let serial = serial.to_lowercase();
+        if serial.len() == 16 && (serial.starts_with("10000000") || serial.starts_with("00000000"))
+        {
+            return Ok(SerialNumber(serial[8..].to_string()));
+        }
+        Ok(SerialNumber(serial))
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl fmt::Display for SerialNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for SerialNumber {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SerialNumber::parse(s)
+    }
+}
+
+impl TryFrom<String> for SerialNumber {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        SerialNumber::parse(&value)
+    }
+}
+
+impl From<SerialNumber> for String {
+    fn from(serial_number: SerialNumber) -> Self {
+        serial_number.0
+    }
+}
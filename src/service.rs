@@ -1,13 +1,81 @@
 use crate::architecture::Architecture;
 use crate::utils::get_random_name;
+use crate::yaml_schema;
+use crate::yaml_schema::{Field, Kind};
 use chrono::{NaiveDateTime, Utc};
 use rusqlite::Row;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::convert::TryInto;
 use yaml_rust::yaml::Hash;
 use yaml_rust::Yaml;
 
+const FILE_SCHEMA: Kind = Kind::Mapping(&[
+    Field::required("path", Kind::String),
+    Field::required("content", Kind::String),
+    Field::optional("mode", Kind::Mode),
+]);
+
+const VOLUME_SCHEMA: Kind = Kind::Mapping(&[
+    Field::required("name", Kind::String),
+    Field::required("path", Kind::String),
+    Field::optional("read-only", Kind::Boolean),
+]);
+
+/// The keys a service may have in a deployment file.
+pub const SCHEMA: Kind = Kind::Mapping(&[
+    Field::required("image", Kind::String),
+    Field::optional("hostname", Kind::String),
+    Field::optional("replicas", Kind::Integer),
+    Field::optional("node", Kind::String),
+    Field::optional("ipv4-address", Kind::Ipv4Address),
+    Field::optional("environment", Kind::MapOf(&Kind::Scalar)),
+    Field::optional("command", Kind::Strings),
+    Field::optional("entrypoint", Kind::Strings),
+    Field::optional("files", Kind::SequenceOf(&FILE_SCHEMA)),
+    Field::optional("volumes", Kind::SequenceOf(&VOLUME_SCHEMA)),
+]);
+
+/// A file written into the container before the service starts.
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+pub struct ServiceFile {
+    pub path: String,
+    pub content: String,
+    /// The permissions as four octal digits, such as `0644`.
+    pub mode: Option<String>,
+}
+
+impl ServiceFile {
+    #[must_use]
+    pub fn from_yaml(yaml: &Yaml) -> Self {
+        ServiceFile {
+            path: String::from(yaml["path"].as_str().unwrap_or_default()),
+            content: String::from(yaml["content"].as_str().unwrap_or_default()),
+            mode: yaml_schema::get_mode(&yaml["mode"]),
+        }
+    }
+}
+
+/// A directory on the local storage of the node that outlives the service, mounted at `path`.
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+pub struct Volume {
+    pub name: String,
+    pub path: String,
+    pub read_only: bool,
+}
+
+impl Volume {
+    #[must_use]
+    pub fn from_yaml(yaml: &Yaml) -> Self {
+        Volume {
+            name: String::from(yaml["name"].as_str().unwrap_or_default()),
+            path: String::from(yaml["path"].as_str().unwrap_or_default()),
+            read_only: yaml["read-only"].as_bool().unwrap_or(false),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Service {
     pub id: Option<i64>,
@@ -22,6 +90,18 @@ pub struct Service {
     pub end: Option<NaiveDateTime>,
     pub node: Option<String>,
     pub architecture: Option<Architecture>,
+    #[serde(default)]
+    pub environment: BTreeMap<String, String>,
+    /// Replaces the command of the image; one given as a string runs with `/bin/sh -c`.
+    #[serde(default)]
+    pub command: Option<Vec<String>>,
+    /// Replaces the entrypoint of the image, read like `command`.
+    #[serde(default)]
+    pub entrypoint: Option<Vec<String>>,
+    #[serde(default)]
+    pub files: Vec<ServiceFile>,
+    #[serde(default)]
+    pub volumes: Vec<Volume>,
 }
 
 impl Service {
@@ -40,6 +120,11 @@ impl Service {
             end: None,
             node: None,
             architecture: None,
+            environment: BTreeMap::new(),
+            command: None,
+            entrypoint: None,
+            files: Vec::new(),
+            volumes: Vec::new(),
         }
     }
 
@@ -65,6 +150,18 @@ impl Service {
         let ipv4_address = hash
             .get(&Yaml::from_str("ipv4-address"))
             .map(|ip| ip.as_str().unwrap_or_default().to_string());
+        let mut environment = BTreeMap::new();
+        if let Some(Yaml::Hash(variables)) = hash.get(&Yaml::from_str("environment")) {
+            for (name, value) in variables {
+                if let (Some(name), Some(value)) = (name.as_str(), yaml_schema::get_scalar(value)) {
+                    environment.insert(String::from(name), value);
+                }
+            }
+        }
+        let get_sequence = |key: &str| match hash.get(&Yaml::from_str(key)) {
+            Some(Yaml::Array(values)) => values.clone(),
+            _ => Vec::new(),
+        };
         Service {
             id: None,
             name: String::from(name),
@@ -78,9 +175,27 @@ impl Service {
             end: None,
             node: None,
             architecture: None,
+            environment,
+            command: hash
+                .get(&Yaml::from_str("command"))
+                .and_then(yaml_schema::get_strings),
+            entrypoint: hash
+                .get(&Yaml::from_str("entrypoint"))
+                .and_then(yaml_schema::get_strings),
+            files: get_sequence("files")
+                .iter()
+                .map(ServiceFile::from_yaml)
+                .collect(),
+            volumes: get_sequence("volumes")
+                .iter()
+                .map(Volume::from_yaml)
+                .collect(),
         }
     }
 
+    /// Expects the columns `id`, `name`, `image`, `deployment`, `node`, `start`, `end`,
+    /// `ipv4_address`, `hostname` and `architecture`, optionally followed by `environment`,
+    /// `command`, `entrypoint`, `files` and `volumes` stored as JSON.
     #[must_use]
     pub fn from_row(row: &Row) -> Self {
         let arch: String = row.get(9).unwrap_or_else(|_| String::from("unknown"));
@@ -97,9 +212,27 @@ impl Service {
             replicas: 1,
             preferred_node: None,
             architecture: Some(Architecture::parse(&arch).unwrap_or(Architecture::ARM64)),
+            environment: get_json(row, 10),
+            command: get_json(row, 11),
+            entrypoint: get_json(row, 12),
+            files: get_json(row, 13),
+            volumes: get_json(row, 14),
         }
     }
 
+    /// Returns the `environment`, `command`, `entrypoint`, `files` and `volumes` columns as
+    /// JSON.
+    #[must_use]
+    pub fn get_json_columns(&self) -> Vec<String> {
+        vec![
+            serde_json::to_string(&self.environment).unwrap_or_default(),
+            serde_json::to_string(&self.command).unwrap_or_default(),
+            serde_json::to_string(&self.entrypoint).unwrap_or_default(),
+            serde_json::to_string(&self.files).unwrap_or_default(),
+            serde_json::to_string(&self.volumes).unwrap_or_default(),
+        ]
+    }
+
     #[must_use]
     pub fn group_services(services: Vec<Service>) -> Vec<Service> {
         let mut groups: HashMap<String, Vec<Service>> = HashMap::new();
@@ -123,3 +256,9 @@ impl Service {
         vec
     }
 }
+
+fn get_json<T: DeserializeOwned + Default>(row: &Row, index: usize) -> T {
+    let json: Option<String> = row.get(index).unwrap_or(None);
+    json.and_then(|j| serde_json::from_str(&j).ok())
+        .unwrap_or_default()
+}
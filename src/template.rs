@@ -0,0 +1,72 @@
+/// Returns the names of the `{name}` placeholders in `template`. `{{` and `}}` stand for
+/// literal braces.
+///
+/// # Errors
+///
+/// Will return `Err` if a brace is not closed or a placeholder is not a valid name
+pub fn get_placeholders(template: &str) -> Result<Vec<String>, String> {
+    Ok(parse(template)?
+        .into_iter()
+        .filter_map(|(_, placeholder)| placeholder)
+        .collect())
+}
+
+/// Replaces every placeholder of `template` with the value `lookup` returns for it.
+///
+/// # Errors
+///
+/// Will return `Err` if `template` is malformed or `lookup` knows no value for a placeholder
+pub fn render<F>(template: &str, lookup: F) -> Result<String, String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut rendered = String::new();
+    for (text, placeholder) in parse(template)? {
+        rendered.push_str(&text);
+        if let Some(placeholder) = placeholder {
+            let value = lookup(&placeholder)
+                .ok_or_else(|| format!("{{{}}} is not a node field or label", placeholder))?;
+            rendered.push_str(&value);
+        }
+    }
+    Ok(rendered)
+}
+
+/// Splits `template` into pieces of text, each followed by the placeholder after it.
+fn parse(template: &str) -> Result<Vec<(String, Option<String>)>, String> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        text.push_str(&rest[..start]);
+        let escape = if rest[start..].starts_with('{') {
+            "{{"
+        } else {
+            "}}"
+        };
+        if rest[start..].starts_with(escape) {
+            text.push_str(&escape[..1]);
+            rest = &rest[start + 2..];
+            continue;
+        }
+        if rest[start..].starts_with('}') {
+            return Err(format!("{:?} has a }} without {{", template));
+        }
+        let length = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("{:?} has a {{ without }}", template))?;
+        let name = &rest[start + 1..start + length];
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(format!("{:?} is not a valid placeholder", name));
+        }
+        pieces.push((std::mem::take(&mut text), Some(String::from(name))));
+        rest = &rest[start + length + 1..];
+    }
+    text.push_str(rest);
+    pieces.push((text, None));
+    Ok(pieces)
+}
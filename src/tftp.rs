@@ -0,0 +1,449 @@
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io;
+use std::io::{ErrorKind, Read};
+use std::net::{SocketAddr, UdpSocket};
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+use crate::node::Node;
+use crate::utils;
+
+const RRQ: u16 = 1;
+const WRQ: u16 = 2;
+const DATA: u16 = 3;
+const ACK: u16 = 4;
+const ERROR: u16 = 5;
+const OACK: u16 = 6;
+
+const DEFAULT_BLOCK_SIZE: usize = 512;
+const MIN_BLOCK_SIZE: usize = 8;
+const MAX_BLOCK_SIZE: usize = 65464;
+const MAX_PACKET_SIZE: usize = MAX_BLOCK_SIZE + 4;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum ErrorCode {
+    FileNotFound = 1,
+    AccessViolation = 2,
+    IllegalOperation = 4,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Mode {
+    Octet,
+    NetAscii,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct ReadRequest {
+    pub filename: String,
+    pub mode: Mode,
+    pub block_size: Option<usize>,
+    pub transfer_size: bool,
+    pub timeout: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Transfer {
+    pub node: Option<String>,
+    pub peer: SocketAddr,
+    pub filename: String,
+    pub path: PathBuf,
+    pub bytes: u64,
+    pub success: bool,
+}
+
+pub struct TftpServer {
+    socket: UdpSocket,
+    root: PathBuf,
+    nodes: Vec<Node>,
+    timeout: Duration,
+    retries: usize,
+    listener: Option<Sender<Transfer>>,
+}
+
+impl TftpServer {
+    /// # Errors
+    ///
+    /// Will return `Err` if `address` could not be bound
+    pub fn bind(address: &str, root: &str, nodes: Vec<Node>) -> io::Result<Self> {
+        Ok(TftpServer {
+            socket: UdpSocket::bind(address)?,
+            root: PathBuf::from(root),
+            nodes,
+            timeout: Duration::from_secs(3),
+            retries: 5,
+            listener: None,
+        })
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if the socket address could not be determined
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    pub fn set_retries(&mut self, retries: usize) {
+        self.retries = retries;
+    }
+
+    pub fn set_listener(&mut self, listener: Sender<Transfer>) {
+        self.listener = Some(listener);
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if the server socket fails
+    pub fn serve(&self) -> io::Result<()> {
+        loop {
+            self.handle_request()?;
+        }
+    }
+
+    /// Receives a single request and serves it on a separate thread.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the server socket fails
+    pub fn handle_request(&self) -> io::Result<()> {
+        let mut buffer = vec![0; MAX_PACKET_SIZE];
+        let (size, peer) = self.socket.recv_from(&mut buffer)?;
+        let packet = &buffer[..size];
+        match opcode(packet) {
+            Some(RRQ) => match ReadRequest::parse(&packet[2..]) {
+                Ok(request) => self.spawn_transfer(request, peer),
+                Err(e) => send_error(&self.socket, peer, ErrorCode::IllegalOperation, e),
+            },
+            Some(WRQ) => send_error(
+                &self.socket,
+                peer,
+                ErrorCode::AccessViolation,
+                "server is read-only",
+            ),
+            _ => send_error(
+                &self.socket,
+                peer,
+                ErrorCode::IllegalOperation,
+                "unexpected packet",
+            ),
+        }
+        Ok(())
+    }
+
+    fn spawn_transfer(&self, request: ReadRequest, peer: SocketAddr) {
+        let node = resolve_node(&self.nodes, &request.filename, peer);
+        let path = match self.resolve_path(&request.filename, node) {
+            Ok(path) => path,
+            Err(e) => {
+                send_error(&self.socket, peer, ErrorCode::AccessViolation, e);
+                return;
+            }
+        };
+        let file = if let Ok(file) = File::open(&path) {
+            file
+        } else {
+            send_error(
+                &self.socket,
+                peer,
+                ErrorCode::FileNotFound,
+                "file not found",
+            );
+            self.log(Transfer {
+                node: node.map(|n| n.id.clone()),
+                peer,
+                filename: request.filename,
+                path,
+                bytes: 0,
+                success: false,
+            });
+            return;
+        };
+        let local = match self.socket.local_addr() {
+            Ok(address) => SocketAddr::new(address.ip(), 0),
+            Err(_) => return,
+        };
+        let timeout = request.timeout.map_or(self.timeout, Duration::from_secs);
+        let retries = self.retries;
+        let listener = self.listener.clone();
+        let node = node.map(|n| n.id.clone());
+        thread::spawn(move || {
+            let result = UdpSocket::bind(local).and_then(|socket| {
+                socket.connect(peer)?;
+                socket.set_read_timeout(Some(timeout))?;
+                send_file(&socket, file, &request, retries)
+            });
+            let transfer = Transfer {
+                node,
+                peer,
+                filename: request.filename,
+                path,
+                bytes: *result.as_ref().unwrap_or(&0),
+                success: result.is_ok(),
+            };
+            log_transfer(&transfer);
+            if let Some(listener) = listener {
+                // The transfer is logged either way, so a listener that went away is ignored.
+                let _ = listener.send(transfer);
+            }
+        });
+    }
+
+    fn resolve_path(&self, filename: &str, node: Option<&Node>) -> Result<PathBuf, &'static str> {
+        let relative = sanitize(filename)?;
+        if let Some(node) = node {
+            if !relative.starts_with(&node.tftp_prefix) {
+                let prefixed = self.root.join(&node.tftp_prefix).join(&relative);
+                if prefixed.is_file() {
+                    return Ok(prefixed);
+                }
+            }
+        }
+        Ok(self.root.join(relative))
+    }
+
+    fn log(&self, transfer: Transfer) {
+        log_transfer(&transfer);
+        if let Some(listener) = &self.listener {
+            let _ = listener.send(transfer);
+        }
+    }
+}
+
+impl ReadRequest {
+    /// # Errors
+    ///
+    /// Will return `Err` if `payload` is not a valid read request
+    pub fn parse(payload: &[u8]) -> Result<ReadRequest, &'static str> {
+        let fields = payload
+            .split(|b| *b == 0)
+            .map(|f| String::from_utf8_lossy(f).to_string())
+            .collect::<Vec<String>>();
+        // a well-formed request ends with a zero byte, which leaves an empty last field
+        if fields.len() < 3 || fields.len() % 2 == 0 || !fields[fields.len() - 1].is_empty() {
+            return Err("malformed request");
+        }
+        let filename = fields[0].clone();
+        if filename.is_empty() {
+            return Err("missing filename");
+        }
+        let mode = match fields[1].to_lowercase().as_str() {
+            "octet" => Mode::Octet,
+            "netascii" => Mode::NetAscii,
+            _ => return Err("unsupported transfer mode"),
+        };
+        let mut request = ReadRequest {
+            filename,
+            mode,
+            block_size: None,
+            transfer_size: false,
+            timeout: None,
+        };
+        for option in fields[2..fields.len() - 1].chunks(2) {
+            let value = option[1].parse::<u64>().ok();
+            match option[0].to_lowercase().as_str() {
+                "blksize" => {
+                    request.block_size = value.and_then(|v| {
+                        usize::try_from(v)
+                            .ok()
+                            .filter(|v| *v >= MIN_BLOCK_SIZE)
+                            .map(|v| v.min(MAX_BLOCK_SIZE))
+                    });
+                }
+                "tsize" => request.transfer_size = value.is_some(),
+                "timeout" => request.timeout = value.filter(|v| (1..=255).contains(v)),
+                _ => {}
+            }
+        }
+        Ok(request)
+    }
+
+    fn has_options(&self) -> bool {
+        self.block_size.is_some() || self.transfer_size || self.timeout.is_some()
+    }
+}
+
+fn opcode(packet: &[u8]) -> Option<u16> {
+    if packet.len() < 2 {
+        return None;
+    }
+    Some(u16::from_be_bytes([packet[0], packet[1]]))
+}
+
+fn sanitize(filename: &str) -> Result<PathBuf, &'static str> {
+    let mut path = PathBuf::new();
+    for component in Path::new(filename.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            _ => return Err("access outside of the tftp root"),
+        }
+    }
+    if path.as_os_str().is_empty() {
+        return Err("missing filename");
+    }
+    Ok(path)
+}
+
+fn resolve_node<'a>(nodes: &'a [Node], filename: &str, peer: SocketAddr) -> Option<&'a Node> {
+    let first = filename
+        .trim_start_matches('/')
+        .split('/')
+        .next()
+        .unwrap_or_default();
+    let lowercase = filename.to_lowercase();
+    nodes
+        .iter()
+        .find(|n| n.tftp_prefix.eq(first))
+        .or_else(|| {
+            nodes.iter().find(|n| {
+                lowercase.starts_with("pxelinux.cfg/")
+                    && lowercase.ends_with(&n.mac_address.to_dashed())
+            })
+        })
+        .or_else(|| nodes.iter().find(|n| peer.ip() == n.ipv4_address))
+}
+
+fn log_transfer(transfer: &Transfer) {
+    let node = match &transfer.node {
+        Some(id) => format!("node {}", id),
+        None => format!("unknown node {}", transfer.peer.ip()),
+    };
+    if transfer.success {
+        utils::print_information(&format!(
+            "{} fetched {} ({} bytes)",
+            node, transfer.filename, transfer.bytes
+        ));
+    } else {
+        utils::print_information(&format!("{} failed to fetch {}", node, transfer.filename));
+    }
+}
+
+fn send_error(socket: &UdpSocket, peer: SocketAddr, code: ErrorCode, message: &str) {
+    let mut packet = Vec::with_capacity(message.len() + 5);
+    packet.extend_from_slice(&ERROR.to_be_bytes());
+    packet.extend_from_slice(&(code as u16).to_be_bytes());
+    packet.extend_from_slice(message.as_bytes());
+    packet.push(0);
+    // The client gives up on its own if the error does not arrive.
+    let _ = socket.send_to(&packet, peer);
+}
+
+fn to_netascii(data: &[u8]) -> Vec<u8> {
+    let mut converted = Vec::with_capacity(data.len());
+    for byte in data {
+        match byte {
+            b'\n' => converted.extend_from_slice(b"\r\n"),
+            b'\r' => converted.extend_from_slice(b"\r\0"),
+            _ => converted.push(*byte),
+        }
+    }
+    converted
+}
+
+fn send_file(
+    socket: &UdpSocket,
+    mut file: File,
+    request: &ReadRequest,
+    retries: usize,
+) -> io::Result<u64> {
+    let (mut source, length): (Box<dyn Read>, u64) = match request.mode {
+        Mode::Octet => {
+            let length = file.metadata()?.len();
+            (Box::new(file), length)
+        }
+        Mode::NetAscii => {
+            let mut data = Vec::new();
+            file.read_to_end(&mut data)?;
+            let data = to_netascii(&data);
+            let length = data.len() as u64;
+            (Box::new(io::Cursor::new(data)), length)
+        }
+    };
+    let block_size = request.block_size.unwrap_or(DEFAULT_BLOCK_SIZE);
+    if request.has_options() {
+        let mut oack = OACK.to_be_bytes().to_vec();
+        if let Some(size) = request.block_size {
+            append_option(&mut oack, "blksize", size as u64);
+        }
+        if request.transfer_size {
+            append_option(&mut oack, "tsize", length);
+        }
+        if let Some(timeout) = request.timeout {
+            append_option(&mut oack, "timeout", timeout);
+        }
+        exchange(socket, &oack, 0, retries)?;
+    }
+    let mut block: u16 = 1;
+    let mut total: u64 = 0;
+    let mut buffer = vec![0; block_size];
+    loop {
+        let size = read_block(&mut source, &mut buffer)?;
+        let mut packet = Vec::with_capacity(size + 4);
+        packet.extend_from_slice(&DATA.to_be_bytes());
+        packet.extend_from_slice(&block.to_be_bytes());
+        packet.extend_from_slice(&buffer[..size]);
+        exchange(socket, &packet, block, retries)?;
+        total += size as u64;
+        if size < block_size {
+            return Ok(total);
+        }
+        block = block.wrapping_add(1);
+    }
+}
+
+fn append_option(packet: &mut Vec<u8>, name: &str, value: u64) {
+    packet.extend_from_slice(name.as_bytes());
+    packet.push(0);
+    packet.extend_from_slice(value.to_string().as_bytes());
+    packet.push(0);
+}
+
+fn read_block(source: &mut Box<dyn Read>, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut size = 0;
+    while size < buffer.len() {
+        match source.read(&mut buffer[size..])? {
+            0 => break,
+            n => size += n,
+        }
+    }
+    Ok(size)
+}
+
+fn exchange(socket: &UdpSocket, packet: &[u8], block: u16, retries: usize) -> io::Result<()> {
+    let mut buffer = [0; 516];
+    for _ in 0..=retries {
+        socket.send(packet)?;
+        loop {
+            let size = match socket.recv(&mut buffer) {
+                Ok(size) => size,
+                Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                    break
+                }
+                Err(e) => return Err(e),
+            };
+            match opcode(&buffer[..size]) {
+                Some(ACK) if size >= 4 && u16::from_be_bytes([buffer[2], buffer[3]]) == block => {
+                    return Ok(());
+                }
+                Some(ERROR) => {
+                    return Err(io::Error::new(
+                        ErrorKind::ConnectionAborted,
+                        "transfer aborted by client",
+                    ))
+                }
+                _ => {}
+            }
+        }
+    }
+    Err(io::Error::new(
+        ErrorKind::TimedOut,
+        "no acknowledgement received",
+    ))
+}
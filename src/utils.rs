@@ -1,8 +1,12 @@
 use std::fs::OpenOptions;
 use std::io;
-use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::mem;
 use std::path::Path;
-use std::process::Command;
+use std::process::{Command, Output, Stdio};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
 use std::{format, fs, print, println, str, usize};
 
 use chrono::NaiveDateTime;
@@ -12,6 +16,10 @@ use names::Generator;
 use prettytable::Cell;
 use string_builder::Builder;
 
+use crate::command_runner::{CommandRunner, LocalRunner};
+use crate::dry_run;
+use crate::dry_run::Effect;
+
 #[must_use]
 pub fn vec_to_string(vec: &[String], quotes: bool) -> String {
     let mut builder = Builder::default();
@@ -103,6 +111,9 @@ pub fn print_message(message: &str, status: bool) {
 ///
 /// Will return `Err` if `filename` could not be written to
 pub fn append_to_file(filename: &str, line: String) -> io::Result<()> {
+    if record_edit(filename, format!("append {:?}", line)) {
+        return Ok(());
+    }
     if let Ok(file) = OpenOptions::new().read(true).write(true).open(filename) {
         let mut lines = BufReader::new(file)
             .lines()
@@ -124,6 +135,9 @@ pub fn append_to_file(filename: &str, line: String) -> io::Result<()> {
 ///
 /// Will return `Err` if `filename` could not be written to
 pub fn remove_line_from_file(filename: &str, line: &str) -> io::Result<()> {
+    if record_edit(filename, format!("remove {:?}", line)) {
+        return Ok(());
+    }
     if let Ok(file) = OpenOptions::new().read(true).write(true).open(filename) {
         let lines = BufReader::new(file)
             .lines()
@@ -146,6 +160,9 @@ pub fn remove_line_from_file(filename: &str, line: &str) -> io::Result<()> {
 ///
 /// Will return `Err` if `filename` could not be written to
 pub fn remove_line_with_substring_from_file(filename: &str, substring: &str) -> io::Result<()> {
+    if record_edit(filename, format!("remove lines containing {:?}", substring)) {
+        return Ok(());
+    }
     if let Ok(file) = OpenOptions::new().read(true).write(true).open(filename) {
         let lines = BufReader::new(file)
             .lines()
@@ -183,6 +200,12 @@ pub fn get_lines_from_file(filename: &str) -> Result<Vec<String>, std::io::Error
 ///
 /// Will return `Err` if `filename` could not be written to
 pub fn replace_in_file(filename: &str, needle: &str, replacement: &str) -> io::Result<()> {
+    if record_edit(
+        filename,
+        format!("replace {:?} with {:?}", needle, replacement),
+    ) {
+        return Ok(());
+    }
     if let Ok(file) = OpenOptions::new().read(true).write(true).open(filename) {
         let lines = BufReader::new(file)
             .lines()
@@ -263,20 +286,20 @@ pub fn read_file(filename: &str) -> Result<String, std::io::Error> {
     }
 }
 
+/// Hashes a file of this host, whatever runner power commands use.
 #[must_use]
 pub fn sha256sum_of_file(filepath: &str) -> Option<String> {
+    sha256sum_of_file_with(&LocalRunner::default(), filepath)
+}
+
+/// Like [`sha256sum_of_file`], but runs `sha256sum` with `runner`.
+#[must_use]
+pub fn sha256sum_of_file_with(runner: &dyn CommandRunner, filepath: &str) -> Option<String> {
     let path = Path::new(filepath);
     if path.exists() {
-        if let Ok(output) = Command::new("sha256sum").arg(filepath).output() {
-            if output.status.success() {
-                if let Ok(str) = str::from_utf8(&output.stdout) {
-                    let sum = str.split(' ').collect::<Vec<&str>>();
-                    if let Some(sum) = sum.first() {
-                        if let Ok(sum) = sum.parse() {
-                            return Some(sum);
-                        }
-                    }
-                }
+        if let Ok(output) = runner.run("sha256sum", &[String::from(filepath)], None) {
+            if output.is_success() {
+                return output.stdout.split(' ').next().map(str::to_string);
             }
         }
     }
@@ -290,3 +313,217 @@ pub fn sha256sum_matches(filepath: &str, checksum: &str) -> bool {
         None => false,
     }
 }
+
+/// Replaces the lines from `begin` to `end` (inclusive) in `filename` with `block`, appending
+/// `block` if the markers are missing. Returns whether the file was changed.
+///
+/// # Errors
+///
+/// Will return `Err` if `filename` could not be read or written to
+pub fn replace_block_in_file(
+    filename: &str,
+    begin: &str,
+    end: &str,
+    block: &str,
+) -> io::Result<bool> {
+    let current = match fs::read_to_string(filename) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let mut lines = current.lines().map(str::to_string).collect::<Vec<String>>();
+    let replacement = block.lines().map(str::to_string).collect::<Vec<String>>();
+    let start = lines.iter().position(|l| l.eq(begin));
+    let stop = lines.iter().position(|l| l.eq(end));
+    match (start, stop) {
+        (Some(start), Some(stop)) if start <= stop => {
+            lines.splice(start..=stop, replacement);
+        }
+        _ => lines.extend(replacement),
+    }
+    let mut updated = lines.join("\n");
+    updated.push('\n');
+    if updated.eq(&current) {
+        return Ok(false);
+    }
+    write_file(filename, &updated)?;
+    Ok(true)
+}
+
+/// Writes `content` to `filename`, or records it in dry-run.
+///
+/// # Errors
+///
+/// Will return `Err` if `filename` could not be written to
+pub fn write_file(filename: &str, content: &str) -> io::Result<()> {
+    if dry_run::record(Effect::WriteFile {
+        filename: filename.to_string(),
+        content: content.to_string(),
+    }) {
+        return Ok(());
+    }
+    fs::write(filename, content)
+}
+
+/// Copies `source` to `destination`, or records it in dry-run.
+///
+/// # Errors
+///
+/// Will return `Err` if `source` could not be copied
+pub fn copy_file(source: &Path, destination: &Path) -> io::Result<()> {
+    if dry_run::record(Effect::CopyFile {
+        source: source.to_string_lossy().to_string(),
+        destination: destination.to_string_lossy().to_string(),
+    }) {
+        return Ok(());
+    }
+    fs::copy(source, destination).map(|_| ())
+}
+
+/// Creates `directory` and its parents, or records it in dry-run.
+///
+/// # Errors
+///
+/// Will return `Err` if `directory` could not be created
+pub fn create_directory(directory: &Path) -> io::Result<()> {
+    if dry_run::record(Effect::CreateDirectory {
+        directory: directory.to_string_lossy().to_string(),
+    }) {
+        return Ok(());
+    }
+    fs::create_dir_all(directory)
+}
+
+fn record_edit(filename: &str, change: String) -> bool {
+    dry_run::record(Effect::EditFile {
+        filename: filename.to_string(),
+        change,
+    })
+}
+
+/// How long the output of a command that exited is read, in case a process it started keeps
+/// its pipes open.
+const PIPE_GRACE: Duration = Duration::from_millis(500);
+
+/// A pipe that is being read on a thread of its own, and what was read so far.
+type Drain = (Arc<Mutex<Vec<u8>>>, thread::JoinHandle<()>);
+
+/// Runs `command` and kills it if it has not exited after `timeout`. Its output is read on
+/// separate threads while it runs, so a command cannot block on a full pipe. Output that a
+/// process started by `command` writes more than [`PIPE_GRACE`] after `command` exited is
+/// ignored; the process is left running.
+///
+/// # Errors
+///
+/// Will return `Err` if `command` could not be started or did not exit in time
+pub fn run_with_timeout(command: &mut Command, timeout: Duration) -> io::Result<Output> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if started.elapsed() >= timeout {
+            child.kill()?;
+            child.wait()?;
+            return Err(io::Error::new(
+                ErrorKind::TimedOut,
+                format!("command did not exit within {} ms", timeout.as_millis()),
+            ));
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    let deadline = Instant::now() + PIPE_GRACE;
+    Ok(Output {
+        status,
+        stdout: take_output(stdout, deadline),
+        stderr: take_output(stderr, deadline),
+    })
+}
+
+/// Reads `pipe` on a new thread until it is closed or fails.
+fn drain<R: Read + Send + 'static>(pipe: Option<R>) -> Drain {
+    let buffer = Arc::new(Mutex::new(Vec::new()));
+    let output = Arc::clone(&buffer);
+    let reader = thread::spawn(move || {
+        if let Some(mut pipe) = pipe {
+            let mut chunk = [0; 4096];
+            loop {
+                match pipe.read(&mut chunk) {
+                    Ok(0) => break,
+                    Ok(size) => output
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .extend_from_slice(&chunk[..size]),
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(_) => break,
+                }
+            }
+        }
+    });
+    (buffer, reader)
+}
+
+/// Waits until `deadline` at most for the pipe to be read to its end and returns what was
+/// read, leaving the thread to itself if the pipe is still open.
+fn take_output((buffer, reader): Drain, deadline: Instant) -> Vec<u8> {
+    while !reader.is_finished() && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+    mem::take(&mut *buffer.lock().unwrap_or_else(PoisonError::into_inner))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command_runner::{CommandOutput, RecordingRunner};
+
+    #[test]
+    fn sha256sum_runs_with_the_runner() {
+        let runner = RecordingRunner::new()
+            .with_output("sha256sum", CommandOutput::new(0, "e3b0c442  src/lib.rs\n"));
+        assert_eq!(
+            sha256sum_of_file_with(&runner, "src/lib.rs").as_deref(),
+            Some("e3b0c442")
+        );
+        assert_eq!(runner.get_calls(), vec![vec!["sha256sum", "src/lib.rs"]]);
+        assert_eq!(sha256sum_of_file_with(&runner, "src/missing.rs"), None);
+        assert_eq!(runner.get_calls().len(), 1);
+    }
+
+    #[test]
+    fn run_with_timeout_reads_large_output() {
+        let output = run_with_timeout(
+            Command::new("sh").args(["-c", "head -c 1000000 /dev/zero; echo error >&2"]),
+            Duration::from_secs(10),
+        )
+        .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout.len(), 1_000_000);
+        assert_eq!(output.stderr, b"error\n");
+    }
+
+    #[test]
+    fn run_with_timeout_ignores_pipes_kept_open_by_other_processes() {
+        let started = Instant::now();
+        let output = run_with_timeout(
+            Command::new("sh").args(["-c", "echo started; sleep 30 & exit 0"]),
+            Duration::from_secs(10),
+        )
+        .unwrap();
+        assert_eq!(output.stdout, b"started\n");
+        let error = run_with_timeout(
+            Command::new("sh").args(["-c", "sleep 30 & sleep 30"]),
+            Duration::from_millis(200),
+        )
+        .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,413 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::Ipv4Addr;
+
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::{Marker, ScanError};
+use yaml_rust::{Yaml, YamlLoader};
+
+/// What a value must look like.
+#[derive(Debug, Clone, Copy)]
+pub enum Kind {
+    String,
+    Integer,
+    Boolean,
+    /// A string, number or boolean, read as a string; nothing reads as an empty string.
+    Scalar,
+    /// A sequence of strings, or a string to run with the shell.
+    Strings,
+    Ipv4Address,
+    /// File permissions as up to four octal digits, such as `0644`, quoted or not.
+    Mode,
+    /// A mapping with the given keys and no others.
+    Mapping(&'static [Field]),
+    /// A mapping from arbitrary names to values of the given kind.
+    MapOf(&'static Kind),
+    SequenceOf(&'static Kind),
+}
+
+impl Kind {
+    #[must_use]
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Kind::String => "a string",
+            Kind::Integer => "an integer",
+            Kind::Boolean => "a boolean",
+            Kind::Scalar => "a string, number or boolean",
+            Kind::Strings => "a string or a sequence of strings",
+            Kind::Ipv4Address => "an IPv4 address",
+            Kind::Mode => "octal file permissions",
+            Kind::Mapping(_) | Kind::MapOf(_) => "a mapping",
+            Kind::SequenceOf(_) => "a sequence",
+        }
+    }
+}
+
+/// A key of a mapping.
+#[derive(Debug, Clone, Copy)]
+pub struct Field {
+    pub name: &'static str,
+    pub kind: Kind,
+    pub required: bool,
+}
+
+impl Field {
+    #[must_use]
+    pub const fn required(name: &'static str, kind: Kind) -> Self {
+        Field {
+            name,
+            kind,
+            required: true,
+        }
+    }
+
+    #[must_use]
+    pub const fn optional(name: &'static str, kind: Kind) -> Self {
+        Field {
+            name,
+            kind,
+            required: false,
+        }
+    }
+}
+
+/// A line and column, both counted from 1.
+pub type Position = (usize, usize);
+
+/// A problem with a YAML document, located by line and column, both counted from 1.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SchemaError {
+    pub line: usize,
+    pub column: usize,
+    pub path: String,
+    pub message: String,
+}
+
+impl SchemaError {
+    #[must_use]
+    pub fn new(position: Position, path: &str, message: &str) -> Self {
+        SchemaError {
+            line: position.0,
+            column: position.1,
+            path: String::from(path),
+            message: String::from(message),
+        }
+    }
+}
+
+impl From<ScanError> for SchemaError {
+    fn from(error: ScanError) -> Self {
+        SchemaError::new(get_position(*error.marker()), "", &error.to_string())
+    }
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+#[derive(Debug)]
+enum Frame {
+    Mapping {
+        path: String,
+        key: Option<String>,
+        expecting_key: bool,
+    },
+    Sequence {
+        path: String,
+        index: usize,
+    },
+}
+
+/// Remembers where every key and value of a document starts, by path.
+#[derive(Debug, Default)]
+struct Markers {
+    stack: Vec<Frame>,
+    /// Containers that start at their first child, as the parser marks block mappings and
+    /// sequences only after their first key.
+    pending: Vec<String>,
+    keys: HashMap<String, Position>,
+    values: HashMap<String, Position>,
+}
+
+impl Markers {
+    /// Returns the path of the node starting now and whether it is a mapping key.
+    fn get_path(&self) -> (String, bool) {
+        match self.stack.last() {
+            Some(Frame::Mapping {
+                path,
+                key,
+                expecting_key,
+            }) => {
+                if *expecting_key {
+                    (path.clone(), true)
+                } else {
+                    (join(path, key.as_deref().unwrap_or("?")), false)
+                }
+            }
+            Some(Frame::Sequence { path, index }) => (format!("{}[{}]", path, index), false),
+            None => (String::new(), false),
+        }
+    }
+
+    fn finish_node(&mut self) {
+        match self.stack.last_mut() {
+            Some(Frame::Mapping { expecting_key, .. }) => *expecting_key = !*expecting_key,
+            Some(Frame::Sequence { index, .. }) => *index += 1,
+            None => {}
+        }
+    }
+}
+
+impl MarkedEventReceiver for Markers {
+    fn on_event(&mut self, event: Event, marker: Marker) {
+        let position = get_position(marker);
+        let (path, is_key) = self.get_path();
+        if let Event::Scalar(..) | Event::Alias(_) = event {
+            for pending in self.pending.drain(..) {
+                self.values.insert(pending, position);
+            }
+        }
+        match event {
+            Event::Scalar(value, ..) => {
+                if is_key {
+                    self.keys.insert(join(&path, &value), position);
+                    if let Some(Frame::Mapping { key, .. }) = self.stack.last_mut() {
+                        *key = Some(value);
+                    }
+                } else {
+                    self.values.insert(path, position);
+                }
+                self.finish_node();
+            }
+            Event::Alias(_) => {
+                if !is_key {
+                    self.values.insert(path, position);
+                }
+                self.finish_node();
+            }
+            Event::MappingStart(_) | Event::SequenceStart(_) => {
+                if is_key {
+                    if let Some(Frame::Mapping { key, .. }) = self.stack.last_mut() {
+                        *key = None;
+                    }
+                } else {
+                    self.values.insert(path.clone(), position);
+                    self.pending.push(path.clone());
+                }
+                self.stack.push(match event {
+                    Event::MappingStart(_) => Frame::Mapping {
+                        path,
+                        key: None,
+                        expecting_key: true,
+                    },
+                    _ => Frame::Sequence { path, index: 0 },
+                });
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.pending.clear();
+                self.stack.pop();
+                self.finish_node();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Reads a value checked as [`Kind::Scalar`].
+#[must_use]
+pub fn get_scalar(yaml: &Yaml) -> Option<String> {
+    match yaml {
+        Yaml::String(value) | Yaml::Real(value) => Some(value.clone()),
+        Yaml::Integer(value) => Some(value.to_string()),
+        Yaml::Boolean(value) => Some(value.to_string()),
+        Yaml::Null => Some(String::new()),
+        _ => None,
+    }
+}
+
+/// Reads a value checked as [`Kind::Mode`] as four octal digits. An unquoted `0644` is read
+/// by YAML as the number 644, whose digits are taken as they are.
+#[must_use]
+pub fn get_mode(yaml: &Yaml) -> Option<String> {
+    let digits = match yaml {
+        Yaml::String(value) => value.clone(),
+        Yaml::Integer(value) if *value >= 0 => value.to_string(),
+        _ => return None,
+    };
+    let digits = digits.trim_start_matches('0');
+    if digits.len() > 4 || !digits.chars().all(|c| ('0'..='7').contains(&c)) {
+        return None;
+    }
+    Some(format!("{:0>4}", digits))
+}
+
+/// Reads a value checked as [`Kind::Strings`]. A single string is left to the shell, as in
+/// `["/bin/sh", "-c", value]`, so that its quotes keep their meaning.
+#[must_use]
+pub fn get_strings(yaml: &Yaml) -> Option<Vec<String>> {
+    match yaml {
+        Yaml::String(value) => Some(vec![
+            String::from("/bin/sh"),
+            String::from("-c"),
+            value.clone(),
+        ]),
+        Yaml::Array(values) => values
+            .iter()
+            .map(|v| v.as_str().map(str::to_string))
+            .collect(),
+        _ => None,
+    }
+}
+
+/// Parses the first document of `content` and checks it against `schema`.
+///
+/// # Errors
+///
+/// Will return `Err` with the syntax error of the document, or with every unknown key, value
+/// of the wrong type, missing required key and invalid IPv4 address
+pub fn validate(content: &str, schema: &Kind) -> Result<Yaml, Vec<SchemaError>> {
+    let mut markers = Markers::default();
+    Parser::new(content.chars())
+        .load(&mut markers, false)
+        .map_err(|e| vec![SchemaError::from(e)])?;
+    let yaml = YamlLoader::load_from_str(content)
+        .map_err(|e| vec![SchemaError::from(e)])?
+        .into_iter()
+        .next()
+        .unwrap_or(Yaml::Null);
+    let mut errors = Vec::new();
+    check(&yaml, schema, "", &markers, &mut errors);
+    if errors.is_empty() {
+        Ok(yaml)
+    } else {
+        Err(errors)
+    }
+}
+
+fn check(yaml: &Yaml, kind: &Kind, path: &str, markers: &Markers, errors: &mut Vec<SchemaError>) {
+    // An empty value is marked where the next node starts, so point at its key instead.
+    let position = match yaml {
+        Yaml::Null => markers.keys.get(path),
+        _ => None,
+    }
+    .or_else(|| markers.values.get(path))
+    .copied()
+    .unwrap_or((1, 1));
+    match (kind, yaml) {
+        (Kind::String | Kind::Strings | Kind::Scalar, Yaml::String(_))
+        | (Kind::Integer, Yaml::Integer(_))
+        | (Kind::Boolean, Yaml::Boolean(_))
+        | (Kind::Scalar, Yaml::Integer(_) | Yaml::Real(_) | Yaml::Boolean(_) | Yaml::Null) => {}
+        (Kind::Mode, Yaml::String(_) | Yaml::Integer(_)) => {
+            if get_mode(yaml).is_none() {
+                errors.push(SchemaError::new(
+                    position,
+                    path,
+                    &format!(
+                        "{} is not octal file permissions such as 0644",
+                        get_scalar(yaml).unwrap_or_default()
+                    ),
+                ));
+            }
+        }
+        (Kind::Strings, Yaml::Array(values)) => {
+            for (index, value) in values.iter().enumerate() {
+                let item = format!("{}[{}]", path, index);
+                check(value, &Kind::String, &item, markers, errors);
+            }
+        }
+        (Kind::Ipv4Address, Yaml::String(address)) => {
+            if address.parse::<Ipv4Addr>().is_err() {
+                errors.push(SchemaError::new(
+                    position,
+                    path,
+                    &format!("{} is not a valid IPv4 address", address),
+                ));
+            }
+        }
+        (Kind::Mapping(fields), Yaml::Hash(hash)) => {
+            for (key, value) in hash {
+                let name = key.as_str().unwrap_or("?");
+                let key_path = join(path, name);
+                if let Some(field) = fields.iter().find(|f| f.name.eq(name)) {
+                    check(value, &field.kind, &key_path, markers, errors);
+                } else {
+                    errors.push(SchemaError::new(
+                        markers.keys.get(&key_path).copied().unwrap_or(position),
+                        &key_path,
+                        &format!("unknown key {}", name),
+                    ));
+                }
+            }
+            for field in fields.iter().filter(|f| f.required) {
+                if !hash.contains_key(&Yaml::String(String::from(field.name))) {
+                    errors.push(SchemaError::new(
+                        markers.keys.get(path).copied().unwrap_or(position),
+                        path,
+                        &format!("missing required key {}", field.name),
+                    ));
+                }
+            }
+        }
+        (Kind::MapOf(kind), Yaml::Hash(hash)) => {
+            for (key, value) in hash {
+                match key.as_str() {
+                    Some(name) => check(value, kind, &join(path, name), markers, errors),
+                    None => errors.push(SchemaError::new(position, path, "expected names as keys")),
+                }
+            }
+        }
+        (Kind::SequenceOf(kind), Yaml::Array(values)) => {
+            for (index, value) in values.iter().enumerate() {
+                check(
+                    value,
+                    kind,
+                    &format!("{}[{}]", path, index),
+                    markers,
+                    errors,
+                );
+            }
+        }
+        (kind, yaml) => errors.push(SchemaError::new(
+            position,
+            path,
+            &format!(
+                "expected {}, found {}",
+                kind.get_name(),
+                get_type_name(yaml)
+            ),
+        )),
+    }
+}
+
+fn get_type_name(yaml: &Yaml) -> &'static str {
+    match yaml {
+        Yaml::String(_) => "a string",
+        Yaml::Integer(_) => "an integer",
+        Yaml::Real(_) => "a number",
+        Yaml::Boolean(_) => "a boolean",
+        Yaml::Hash(_) => "a mapping",
+        Yaml::Array(_) => "a sequence",
+        Yaml::Alias(_) => "an alias",
+        Yaml::Null => "nothing",
+        Yaml::BadValue => "an invalid value",
+    }
+}
+
+fn get_position(marker: Marker) -> Position {
+    (marker.line(), marker.col() + 1)
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        String::from(key)
+    } else {
+        format!("{}.{}", path, key)
+    }
+}